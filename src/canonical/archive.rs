@@ -0,0 +1,150 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Cursor, Read, Write};
+use std::path::Path;
+use image::ImageFormat;
+use crate::canonical::{CaptureError, Snapshot};
+
+const MAGIC: &[u8; 8] = b"CAPSNAP\0";
+const VERSION: u32 = 1;
+
+/// The preferred extension of the archive file.
+pub const ARCHIVE_EXTENSION: &str = "capsnap";
+
+fn write_chunk(writer: &mut impl Write, chunk: &[u8]) -> Result<(), CaptureError> {
+    writer.write_all(&(chunk.len() as u64).to_le_bytes())?;
+    writer.write_all(chunk)?;
+    Ok(())
+}
+
+fn read_chunk(reader: &mut impl Read) -> Result<Vec<u8>, CaptureError> {
+    let mut len = [0u8; 8];
+    reader.read_exact(&mut len)?;
+
+    // do not trust the length to allocate everything up front
+    let mut chunk = vec![];
+    reader.take(u64::from_le_bytes(len)).read_to_end(&mut chunk)?;
+    if chunk.len() as u64 != u64::from_le_bytes(len) {
+        return Err(CaptureError::invalid_data("unexpected end of archive"));
+    }
+    Ok(chunk)
+}
+
+impl Snapshot {
+    /// Write the snapshot as a single-file archive, images are stored losslessly (PNG).
+    ///
+    /// Layout (all integers are little-endian):
+    ///
+    /// | Part                  | Content                                  |
+    /// |-----------------------|------------------------------------------|
+    /// | magic                 | `CAPSNAP\0`                              |
+    /// | version               | u32                                      |
+    /// | metadata              | u64 length + JSON of the snapshot        |
+    /// | image * screens.len() | u64 length + PNG of the screen, in order |
+    pub fn write_archive(&self, writer: &mut impl Write) -> Result<(), CaptureError> {
+        writer.write_all(MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+
+        let metadata = serde_json::to_vec(self)?;
+        write_chunk(writer, &metadata)?;
+
+        for screen in &self.screens {
+            let mut buffer = Cursor::new(vec![]);
+            screen.rgba_image.write_to(&mut buffer, ImageFormat::Png)?;
+            write_chunk(writer, buffer.get_ref())?;
+        }
+
+        Ok(())
+    }
+
+    /// Read a snapshot from an archive written by [`Snapshot::write_archive`].
+    pub fn read_archive(reader: &mut impl Read) -> Result<Snapshot, CaptureError> {
+        let mut magic = [0u8; 8];
+        reader.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(CaptureError::invalid_data("not a snapshot archive"));
+        }
+        let mut version = [0u8; 4];
+        reader.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != VERSION {
+            return Err(CaptureError::invalid_data(format!("unsupported archive version: {}", version)));
+        }
+
+        let metadata = read_chunk(reader)?;
+        let Snapshot { mut screens, apps, .. } = serde_json::from_slice(&metadata)?;
+
+        for screen in &mut screens {
+            let buffer = read_chunk(reader)?;
+            screen.rgba_image = image::load_from_memory_with_format(&buffer, ImageFormat::Png)?.into_rgba8();
+        }
+
+        // the bounding box is derived from the screens
+        Snapshot::new(screens, apps)
+    }
+
+    /// Save the snapshot to an archive file.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), CaptureError> {
+        let file = File::create(path)?;
+        let mut writer = BufWriter::new(file);
+        self.write_archive(&mut writer)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    /// Load a snapshot from an archive file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Snapshot, CaptureError> {
+        let file = File::open(path)?;
+        Snapshot::read_archive(&mut BufReader::new(file))
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use image::{Rgba, RgbaImage};
+    use crate::canonical::{AppInfo, ScreenInfo};
+    use super::*;
+
+    #[test]
+    fn archive_round_trip_test() {
+        let snapshot = Snapshot::new(
+            vec![
+                ScreenInfo {
+                    name: "main".into(),
+                    is_primary: true,
+                    xywh: (0, 0, 4, 2),
+                    sf: 1.5,
+                    rgba_image: RgbaImage::from_fn(4, 2, |x, y| Rgba([x as u8, y as u8, 7, 128])),
+                },
+                ScreenInfo {
+                    name: "side".into(),
+                    is_primary: false,
+                    xywh: (-3, 1, 3, 3),
+                    sf: 1.0,
+                    rgba_image: RgbaImage::from_pixel(3, 3, Rgba([1, 2, 3, 4])),
+                },
+            ],
+            vec![AppInfo {
+                name: "app".into(),
+                title: "title".into(),
+                is_minimized: true,
+                xywh: (1, 1, 2, 2),
+                pid: Some(7),
+                ..Default::default()
+            }],
+        ).unwrap();
+
+        let mut buffer = vec![];
+        snapshot.write_archive(&mut buffer).unwrap();
+        let loaded = Snapshot::read_archive(&mut buffer.as_slice()).unwrap();
+
+        assert_eq!(loaded.xywh, snapshot.xywh);
+        assert_eq!(format!("{:?}", loaded), format!("{:?}", snapshot));
+        for (a, b) in loaded.screens.iter().zip(&snapshot.screens) {
+            assert_eq!(a.rgba_image, b.rgba_image);
+        }
+
+        // truncated or foreign data is rejected
+        assert!(Snapshot::read_archive(&mut &buffer[..buffer.len() - 1]).is_err());
+        assert!(Snapshot::read_archive(&mut &b"not an archive"[..]).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use image::imageops::{self, FilterType};
+use image::RgbaImage;
+use crate::canonical::{ScreenInfo, Snapshot};
+
+/// How to treat monitors of different scale factors when composing the desktop.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum ScalePolicy {
+    /// keep the pixels of each monitor as they are
+    #[default]
+    Native,
+    /// upscale each monitor to the largest scale factor
+    UpscaleToMax,
+    /// downscale each monitor to the smallest scale factor
+    DownscaleToMin,
+}
+
+/// Map positions on one axis (in desktop coordinates) to positions in the composed image.
+///
+/// Each segment between two adjacent screen edges is stretched by the largest density (pixels per
+/// desktop unit) of the screens covering it, gaps use the smallest density of all screens.
+/// So the screens stay side by side after being scaled differently.
+struct AxisMap {
+    /// (desktop position, composed position), sorted
+    stops: Vec<(i32, f64)>,
+}
+
+impl AxisMap {
+    /// - spans: (start, length, density) of each screen on this axis
+    fn new(spans: &[(i32, u32, f64)]) -> AxisMap {
+        let mut edges: Vec<i32> = spans.iter()
+            .flat_map(|&(start, len, _)| [start, start + len as i32])
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+
+        let gap_density = spans.iter().map(|s| s.2).fold(f64::INFINITY, f64::min);
+        let mut stops: Vec<(i32, f64)> = edges.first().map(|&e| vec![(e, 0.0)]).unwrap_or_default();
+        for pair in edges.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let density = spans.iter()
+                .filter(|&&(start, len, _)| start <= a && b <= start + len as i32)
+                .map(|s| s.2)
+                .reduce(f64::max)
+                .unwrap_or(gap_density);
+            let last = stops.last().unwrap().1;
+            stops.push((b, last + (b - a) as f64 * density));
+        }
+
+        AxisMap { stops }
+    }
+
+    fn map(&self, position: i32) -> u32 {
+        let index = self.stops.partition_point(|&(p, _)| p < position);
+        self.stops.get(index).map_or(0.0, |s| s.1).round() as u32
+    }
+}
+
+impl Snapshot {
+    /// Stitch the images of all screens into one image, each screen is placed at its offset
+    /// in the bounding box of the snapshot. The area not covered by any screen is transparent.
+    ///
+    /// A screen whose scale factor is not a positive number is taken as 1.0, see [`ScreenInfo::scale_factor`].
+    pub fn compose(&self, policy: ScalePolicy) -> RgbaImage {
+        let (min_sf, max_sf) = self.screens.iter()
+            .map(ScreenInfo::scale_factor)
+            .fold((f32::INFINITY, 0f32), |(min, max), sf| (min.min(sf), max.max(sf)));
+
+        // (target width, target height) of the image of each screen
+        let sizes: Vec<(u32, u32)> = self.screens.iter()
+            .map(|screen| {
+                let factor = match policy {
+                    ScalePolicy::Native => 1.0,
+                    ScalePolicy::UpscaleToMax => max_sf / screen.scale_factor(),
+                    ScalePolicy::DownscaleToMin => min_sf / screen.scale_factor(),
+                };
+                let (w, h) = screen.rgba_image.dimensions();
+                (
+                    ((w as f32 * factor).round() as u32).max(1),
+                    ((h as f32 * factor).round() as u32).max(1),
+                )
+            })
+            .collect();
+
+        let x_map = AxisMap::new(&self.screens.iter().zip(&sizes)
+            .map(|(s, &(w, _))| (s.xywh.0, s.xywh.2, w as f64 / s.xywh.2.max(1) as f64))
+            .collect::<Vec<_>>());
+        let y_map = AxisMap::new(&self.screens.iter().zip(&sizes)
+            .map(|(s, &(_, h))| (s.xywh.1, s.xywh.3, h as f64 / s.xywh.3.max(1) as f64))
+            .collect::<Vec<_>>());
+
+        let placements: Vec<(u32, u32)> = self.screens.iter()
+            .map(|s| (x_map.map(s.xywh.0), y_map.map(s.xywh.1)))
+            .collect();
+        let width = placements.iter().zip(&sizes).map(|(p, s)| p.0 + s.0).max().unwrap_or(0);
+        let height = placements.iter().zip(&sizes).map(|(p, s)| p.1 + s.1).max().unwrap_or(0);
+
+        let mut canvas = RgbaImage::new(width, height);
+        for ((screen, &(w, h)), &(x, y)) in self.screens.iter().zip(&sizes).zip(&placements) {
+            if screen.rgba_image.dimensions() == (w, h) {
+                imageops::replace(&mut canvas, &screen.rgba_image, x as i64, y as i64);
+            } else {
+                let resized = imageops::resize(&screen.rgba_image, w, h, FilterType::CatmullRom);
+                imageops::replace(&mut canvas, &resized, x as i64, y as i64);
+            }
+        }
+        canvas
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use image::Rgba;
+    use crate::canonical::XYWH;
+    use super::*;
+
+    fn screen(xywh: XYWH, sf: f32, color: [u8; 4]) -> ScreenInfo {
+        ScreenInfo {
+            name: format!("{:?}", xywh),
+            is_primary: false,
+            xywh,
+            sf,
+            rgba_image: RgbaImage::from_pixel(xywh.2, xywh.3, Rgba(color)),
+        }
+    }
+
+    #[test]
+    fn compose_native_test() {
+        // negative origin, and a gap between the screens
+        let snapshot = Snapshot::new(
+            vec![
+                screen((-30, -10, 20, 10), 1.0, [255, 0, 0, 255]),
+                screen((0, 0, 10, 10), 1.0, [0, 255, 0, 255]),
+            ],
+            vec![],
+        ).unwrap();
+
+        let image = snapshot.compose(ScalePolicy::Native);
+        assert_eq!(image.dimensions(), (40, 20));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(25, 5).0, [0, 0, 0, 0]);
+        assert_eq!(image.get_pixel(30, 10).0, [0, 255, 0, 255]);
+        assert_eq!(image, snapshot.crop(snapshot.xywh));
+    }
+
+    #[test]
+    fn compose_mixed_scale_factor_test() {
+        // a 100% monitor on the left of a 200% one
+        let snapshot = Snapshot::new(
+            vec![
+                screen((0, 0, 20, 10), 1.0, [255, 0, 0, 255]),
+                screen((20, 0, 40, 20), 2.0, [0, 255, 0, 255]),
+            ],
+            vec![],
+        ).unwrap();
+
+        let image = snapshot.compose(ScalePolicy::UpscaleToMax);
+        assert_eq!(image.dimensions(), (80, 20));
+        assert_eq!(image.get_pixel(39, 19).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(40, 0).0, [0, 255, 0, 255]);
+
+        let image = snapshot.compose(ScalePolicy::DownscaleToMin);
+        assert_eq!(image.dimensions(), (40, 10));
+        assert_eq!(image.get_pixel(19, 9).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(20, 0).0, [0, 255, 0, 255]);
+
+        // a broken scale factor (e.g. from an archive) is taken as 1.0 instead of blowing up the size
+        for sf in [0.0, -2.0, f32::NAN, f32::INFINITY] {
+            let snapshot = Snapshot::new(
+                vec![
+                    screen((0, 0, 20, 10), sf, [255, 0, 0, 255]),
+                    screen((20, 0, 40, 20), 2.0, [0, 255, 0, 255]),
+                ],
+                vec![],
+            ).unwrap();
+            assert_eq!(snapshot.compose(ScalePolicy::UpscaleToMax).dimensions(), (80, 20), "sf {}", sf);
+            assert_eq!(snapshot.compose(ScalePolicy::DownscaleToMin).dimensions(), (40, 10), "sf {}", sf);
+        }
+    }
+}
@@ -0,0 +1,318 @@
+use crate::canonical::{ScreenInfo, Snapshot, XYWH};
+
+/// A point in physical coordinates, that is, the desktop coordinates reported by the
+/// backend (the same space as `xywh`). Fractional values are allowed for pointer positions.
+///
+/// These are the units of the backend, not always pixels: pixels on Windows and X11, but points on macOS.
+/// The image of a screen has [`ScreenInfo::density`] pixels per unit.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct PhysicalPos {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A point in logical coordinates, that is, the points of the cropper UI (the logical coordinates of the platform).
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct LogicalPos {
+    pub x: f32,
+    pub y: f32,
+}
+
+/// A rect in physical coordinates.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct PhysicalRect {
+    pub min: PhysicalPos,
+    pub max: PhysicalPos,
+}
+
+/// A rect in logical coordinates.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct LogicalRect {
+    pub min: LogicalPos,
+    pub max: LogicalPos,
+}
+
+impl PhysicalRect {
+    pub fn from_xywh(xywh: XYWH) -> PhysicalRect {
+        let (x, y, w, h) = xywh;
+        PhysicalRect {
+            min: PhysicalPos { x: x as f32, y: y as f32 },
+            max: PhysicalPos { x: x as f32 + w as f32, y: y as f32 + h as f32 },
+        }
+    }
+
+    /// Snap each edge to the nearest pixel boundary.
+    pub fn to_xywh(self) -> XYWH {
+        let x1 = self.min.x.min(self.max.x).round() as i32;
+        let y1 = self.min.y.min(self.max.y).round() as i32;
+        let x2 = self.min.x.max(self.max.x).round() as i32;
+        let y2 = self.min.y.max(self.max.y).round() as i32;
+        (x1, y1, (x2 - x1) as u32, (y2 - y1) as u32)
+    }
+}
+
+/// The mapping between the logical coordinates of a canvas and the physical coordinates:
+/// the top-left corner of the canvas is at `origin`, and one logical unit is `ppp` physical units.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct CoordinateSpace {
+    pub origin: PhysicalPos,
+    /// physical units per point
+    pub ppp: f32,
+}
+
+impl Default for CoordinateSpace {
+    fn default() -> CoordinateSpace {
+        CoordinateSpace { origin: PhysicalPos::default(), ppp: 1.0 }
+    }
+}
+
+impl CoordinateSpace {
+    pub fn to_physical(self, pos: LogicalPos) -> PhysicalPos {
+        PhysicalPos {
+            x: self.origin.x + pos.x * self.ppp,
+            y: self.origin.y + pos.y * self.ppp,
+        }
+    }
+
+    pub fn to_logical(self, pos: PhysicalPos) -> LogicalPos {
+        LogicalPos {
+            x: (pos.x - self.origin.x) / self.ppp,
+            y: (pos.y - self.origin.y) / self.ppp,
+        }
+    }
+
+    pub fn to_physical_rect(self, rect: LogicalRect) -> PhysicalRect {
+        PhysicalRect { min: self.to_physical(rect.min), max: self.to_physical(rect.max) }
+    }
+
+    pub fn to_logical_rect(self, rect: PhysicalRect) -> LogicalRect {
+        LogicalRect { min: self.to_logical(rect.min), max: self.to_logical(rect.max) }
+    }
+}
+
+/// The mapping between the cropper UI and the desktop.
+///
+/// The UI is a single window, which egui renders with a single `pixels_per_point` whatever the screens under it,
+/// so one [`CoordinateSpace`] covers the whole desktop: a point is `pixels_per_point / density` physical units.
+/// The density (1.0 unless the backend reports points, e.g. on macOS) is the one of the screen the window takes
+/// its scale from, that is, the first screen of that scale factor, or the primary one.
+/// The logical coordinates start at the top-left corner of the desktop.
+#[derive(Clone, PartialEq, Debug)]
+pub struct DesktopSpace {
+    /// the bounding box of the screens
+    bounds: PhysicalRect,
+    pixels_per_point: f32,
+    space: CoordinateSpace,
+}
+
+impl DesktopSpace {
+    /// The mapping of a UI rendered with `pixels_per_point` (1.0 if it is not a positive number).
+    pub fn new(screens: &[ScreenInfo], pixels_per_point: f32) -> DesktopSpace {
+        let pixels_per_point = if pixels_per_point > 0.0 && pixels_per_point.is_finite() { pixels_per_point } else { 1.0 };
+        let screen = screens.iter()
+            .find(|screen| (screen.scale_factor() - pixels_per_point).abs() < 0.01)
+            .or_else(|| screens.iter().find(|screen| screen.is_primary))
+            .or(screens.first());
+        let density = screen.map(|screen| screen.density().0).filter(|density| *density > 0.0).unwrap_or(1.0);
+        let bounds = screens.iter()
+            .map(|screen| PhysicalRect::from_xywh(screen.xywh))
+            .reduce(|a, b| PhysicalRect {
+                min: PhysicalPos { x: a.min.x.min(b.min.x), y: a.min.y.min(b.min.y) },
+                max: PhysicalPos { x: a.max.x.max(b.max.x), y: a.max.y.max(b.max.y) },
+            })
+            .unwrap_or_default();
+
+        let space = CoordinateSpace { origin: bounds.min, ppp: pixels_per_point / density };
+        DesktopSpace { bounds, pixels_per_point, space }
+    }
+
+    /// The mapping before the window is shown, with the scale factor of the primary screen, which the window is created with.
+    pub fn native(screens: &[ScreenInfo]) -> DesktopSpace {
+        let primary = screens.iter().find(|screen| screen.is_primary).or(screens.first());
+        DesktopSpace::new(screens, primary.map_or(1.0, ScreenInfo::scale_factor))
+    }
+
+    pub fn pixels_per_point(&self) -> f32 {
+        self.pixels_per_point
+    }
+
+    /// The mapping of every point of the UI.
+    pub fn space(&self) -> CoordinateSpace {
+        self.space
+    }
+
+    /// Where the window covers the desktop, in points of the UI from the origin of the desktop,
+    /// as the platform places the window (a point is `pixels_per_point` physical pixels of the platform).
+    pub fn viewport(&self) -> LogicalRect {
+        let to_points = |pos: PhysicalPos| LogicalPos { x: pos.x / self.space.ppp, y: pos.y / self.space.ppp };
+        LogicalRect { min: to_points(self.bounds.min), max: to_points(self.bounds.max) }
+    }
+
+    /// The whole UI, from its top-left corner.
+    pub fn canvas(&self) -> LogicalRect {
+        self.space.to_logical_rect(self.bounds)
+    }
+
+    pub fn to_physical(&self, pos: LogicalPos) -> PhysicalPos {
+        self.space.to_physical(pos)
+    }
+
+    pub fn to_logical(&self, pos: PhysicalPos) -> LogicalPos {
+        self.space.to_logical(pos)
+    }
+
+    pub fn to_logical_rect(&self, rect: PhysicalRect) -> LogicalRect {
+        self.space.to_logical_rect(rect)
+    }
+}
+
+impl ScreenInfo {
+    /// The scale factor, 1.0 if the reported one is not a positive number (e.g. read from a broken archive).
+    pub fn scale_factor(&self) -> f32 {
+        if self.sf > 0.0 && self.sf.is_finite() { self.sf } else { 1.0 }
+    }
+
+    /// Pixels of the image per physical unit, on each axis.
+    ///
+    /// This is 1.0 unless the backend reports the screen size in other units than the image (e.g. points on macOS).
+    pub fn density(&self) -> (f32, f32) {
+        let (_, _, w, h) = self.xywh;
+        let (iw, ih) = self.rgba_image.dimensions();
+        (
+            if w == 0 { 1.0 } else { iw as f32 / w as f32 },
+            if h == 0 { 1.0 } else { ih as f32 / h as f32 },
+        )
+    }
+
+    pub fn contains(&self, pos: PhysicalPos) -> bool {
+        let (x, y, w, h) = self.xywh;
+        pos.x >= x as f32 && pos.y >= y as f32 && pos.x < x as f32 + w as f32 && pos.y < y as f32 + h as f32
+    }
+
+    /// The pixel of the image at the point, `None` if the point is not on this screen.
+    pub fn to_image_pixel(&self, pos: PhysicalPos) -> Option<(u32, u32)> {
+        if !self.contains(pos) {
+            return None;
+        }
+
+        let (x, y, _, _) = self.xywh;
+        let (dx, dy) = self.density();
+        let (iw, ih) = self.rgba_image.dimensions();
+        let ix = (((pos.x - x as f32) * dx) as u32).min(iw.saturating_sub(1));
+        let iy = (((pos.y - y as f32) * dy) as u32).min(ih.saturating_sub(1));
+        Some((ix, iy))
+    }
+}
+
+impl Snapshot {
+    /// The (first) screen at the point.
+    pub fn screen_at(&self, pos: PhysicalPos) -> Option<&ScreenInfo> {
+        self.screens.iter().find(|screen| screen.contains(pos))
+    }
+
+    /// The color (in RGBA format) of the pixel at the point, `None` if the point is not on any screen.
+    pub fn pixel_at(&self, pos: PhysicalPos) -> Option<[u8; 4]> {
+        let screen = self.screen_at(pos)?;
+        let (ix, iy) = screen.to_image_pixel(pos)?;
+        Some(screen.rgba_image.get_pixel(ix, iy).0)
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use image::{Rgba, RgbaImage};
+    use super::*;
+
+    #[test]
+    fn coordinate_space_test() {
+        // a 200% UI whose top-left corner is at (-100, 50) on the desktop
+        let space = CoordinateSpace { origin: PhysicalPos { x: -100.0, y: 50.0 }, ppp: 2.0 };
+        let physical = space.to_physical(LogicalPos { x: 10.0, y: 5.5 });
+        assert_eq!(physical, PhysicalPos { x: -80.0, y: 61.0 });
+        assert_eq!(space.to_logical(physical), LogicalPos { x: 10.0, y: 5.5 });
+
+        let rect = PhysicalRect::from_xywh((-100, 50, 30, 20));
+        assert_eq!(space.to_physical_rect(space.to_logical_rect(rect)), rect);
+
+        // edges snap to the nearest pixel, whatever the direction of the rect
+        let rect = PhysicalRect { min: PhysicalPos { x: 9.6, y: 3.4 }, max: PhysicalPos { x: 0.2, y: 0.5 } };
+        assert_eq!(rect.to_xywh(), (0, 1, 10, 2));
+    }
+
+    #[test]
+    fn desktop_space_test() {
+        let screen = |xywh: XYWH, is_primary: bool, sf: f32, image: (u32, u32)| ScreenInfo {
+            name: format!("{:?}", xywh),
+            is_primary,
+            xywh,
+            sf,
+            rgba_image: RgbaImage::new(image.0, image.1),
+        };
+
+        // in points, a 1x primary screen on the left of a 2x one: one point is one unit on both
+        let screens = [screen((0, 0, 100, 100), true, 1.0, (100, 100)), screen((100, 0, 100, 50), false, 2.0, (200, 100))];
+        let space = DesktopSpace::new(&screens, 1.0);
+        assert_eq!(space.viewport(), LogicalRect { min: LogicalPos { x: 0.0, y: 0.0 }, max: LogicalPos { x: 200.0, y: 100.0 } });
+        assert_eq!(space.to_logical(PhysicalPos { x: 150.0, y: 20.0 }), LogicalPos { x: 150.0, y: 20.0 });
+        // still one unit when the window is rendered at 2x
+        assert_eq!(DesktopSpace::new(&screens, 2.0).space(), space.space());
+        assert_eq!(DesktopSpace::native(&screens), space);
+
+        // in pixels, a 200% screen on the left of the 100% primary one, the window follows one of them
+        let screens = [screen((-200, 0, 200, 200), false, 2.0, (200, 200)), screen((0, 0, 100, 100), true, 1.0, (100, 100))];
+        let space = DesktopSpace::native(&screens);
+        assert_eq!(space.pixels_per_point(), 1.0);
+        assert_eq!(space.viewport(), LogicalRect { min: LogicalPos { x: -200.0, y: 0.0 }, max: LogicalPos { x: 100.0, y: 200.0 } });
+        assert_eq!(space.canvas().max, LogicalPos { x: 300.0, y: 200.0 });
+        assert_eq!(space.to_logical(PhysicalPos { x: -100.0, y: 50.0 }), LogicalPos { x: 100.0, y: 50.0 });
+        assert_eq!(space.to_logical(PhysicalPos { x: 50.0, y: 50.0 }), LogicalPos { x: 250.0, y: 50.0 });
+
+        // the same scale on both screens, whichever the pointer is on
+        let space = DesktopSpace::new(&screens, 2.0);
+        assert_eq!(space.viewport(), LogicalRect { min: LogicalPos { x: -100.0, y: 0.0 }, max: LogicalPos { x: 50.0, y: 100.0 } });
+        assert_eq!(space.to_logical(PhysicalPos { x: -100.0, y: 50.0 }), LogicalPos { x: 50.0, y: 25.0 });
+        assert_eq!(space.to_logical(PhysicalPos { x: 50.0, y: 50.0 }), LogicalPos { x: 125.0, y: 25.0 });
+        assert_eq!(space.to_physical(LogicalPos { x: 125.0, y: 25.0 }), PhysicalPos { x: 50.0, y: 50.0 });
+        let rect = PhysicalRect { min: PhysicalPos { x: -10.0, y: 0.0 }, max: PhysicalPos { x: 10.0, y: 10.0 } };
+        assert_eq!(space.to_logical_rect(rect), LogicalRect { min: LogicalPos { x: 95.0, y: 0.0 }, max: LogicalPos { x: 105.0, y: 5.0 } });
+
+        // a broken scale is taken as 1.0
+        assert_eq!(DesktopSpace::new(&screens, 0.0), DesktopSpace::native(&screens));
+    }
+
+    #[test]
+    fn screen_at_test() {
+        // the right screen reports its size in points, with 2x pixels
+        let snapshot = Snapshot::new(
+            vec![
+                ScreenInfo {
+                    name: "left".into(),
+                    is_primary: true,
+                    xywh: (0, 0, 10, 10),
+                    sf: 1.0,
+                    rgba_image: RgbaImage::from_pixel(10, 10, Rgba([0, 0, 0, 255])),
+                },
+                ScreenInfo {
+                    name: "right".into(),
+                    is_primary: false,
+                    xywh: (10, 0, 10, 10),
+                    sf: 2.0,
+                    rgba_image: RgbaImage::from_fn(20, 20, |x, y| Rgba([x as u8, y as u8, 0, 255])),
+                },
+            ],
+            vec![],
+        ).unwrap();
+
+        let pos = PhysicalPos { x: 15.5, y: 9.9 };
+        let screen = snapshot.screen_at(pos).unwrap();
+        assert_eq!(screen.name, "right");
+        assert_eq!(screen.density(), (2.0, 2.0));
+        assert_eq!(screen.to_image_pixel(pos), Some((11, 19)));
+        assert_eq!(snapshot.screens[0].to_image_pixel(pos), None);
+        assert!(snapshot.screen_at(PhysicalPos { x: 20.0, y: 0.0 }).is_none());
+
+        assert_eq!(snapshot.pixel_at(pos), Some([11, 19, 0, 255]));
+        assert_eq!(snapshot.pixel_at(PhysicalPos { x: 5.0, y: 5.0 }), Some([0, 0, 0, 255]));
+        assert_eq!(snapshot.pixel_at(PhysicalPos { x: 20.0, y: 0.0 }), None);
+    }
+}
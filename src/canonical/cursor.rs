@@ -0,0 +1,147 @@
+use image::{imageops, Rgba, RgbaImage};
+use crate::canonical::{CaptureError, PhysicalPos, Snapshot};
+
+/// The mouse cursor, as reported by the backend.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Cursor {
+    /// the hotspot on the desktop
+    pub position: PhysicalPos,
+    /// the cursor image with the hotspot in it, `None` if the backend can not tell (the built-in arrow is used then)
+    pub image: Option<(RgbaImage, (u32, u32))>,
+}
+
+/// How big the cursor is painted on the screens.
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum CursorScale {
+    /// the image of the backend as it is, the built-in arrow by the scale factor of the screen
+    #[default]
+    Auto,
+    /// both by the factor, which must be positive, e.g. to match the cursor size of a HiDPI screen
+    Fixed(f32),
+}
+
+/// The built-in arrow, `X` is the outline, `.` the fill, with the hotspot at the tip.
+const ARROW: [&str; 19] = [
+    "X           ",
+    "XX          ",
+    "X.X         ",
+    "X..X        ",
+    "X...X       ",
+    "X....X      ",
+    "X.....X     ",
+    "X......X    ",
+    "X.......X   ",
+    "X........X  ",
+    "X.........X ",
+    "X......XXXXX",
+    "X...X..X    ",
+    "X..XX..X    ",
+    "X.X  X..X   ",
+    "XX   X..X   ",
+    "X     X..X  ",
+    "      X..X  ",
+    "       XX   ",
+];
+
+/// The built-in arrow, at 1x.
+pub fn arrow_sprite() -> RgbaImage {
+    RgbaImage::from_fn(ARROW[0].len() as u32, ARROW.len() as u32, |x, y| {
+        match ARROW[y as usize].as_bytes()[x as usize] {
+            b'X' => Rgba([0, 0, 0, 255]),
+            b'.' => Rgba([255, 255, 255, 255]),
+            _ => Rgba([0, 0, 0, 0]),
+        }
+    })
+}
+
+impl Snapshot {
+    /// Paint the cursor onto the image of the screen under it, nothing happens if it is on no screen.
+    ///
+    /// Fails with [`CaptureError::InvalidRequest`] if the fixed factor is not a positive number.
+    pub fn draw_cursor(&mut self, cursor: &Cursor, scale: CursorScale) -> Result<(), CaptureError> {
+        if let CursorScale::Fixed(factor) = scale {
+            if !(factor > 0.0 && factor.is_finite()) {
+                return Err(CaptureError::InvalidRequest(format!("invalid cursor scale {}, expect a positive number", factor)));
+            }
+        }
+        let Some(screen) = self.screens.iter_mut().find(|screen| screen.contains(cursor.position)) else {
+            return Ok(());
+        };
+        let Some((px, py)) = screen.to_image_pixel(cursor.position) else {
+            return Ok(());
+        };
+
+        let (image, (hx, hy), auto) = match &cursor.image {
+            Some((image, hotspot)) => (image.clone(), *hotspot, 1.0),
+            None => (arrow_sprite(), (0, 0), screen.scale_factor()),
+        };
+        let factor = match scale {
+            CursorScale::Auto => auto,
+            CursorScale::Fixed(factor) => factor,
+        };
+        let (image, hotspot) = if factor != 1.0 {
+            let (w, h) = image.dimensions();
+            let size = |v: u32| ((v as f32 * factor).round() as u32).max(1);
+            let resized = imageops::resize(&image, size(w), size(h), imageops::FilterType::Nearest);
+            (resized, ((hx as f32 * factor) as i64, (hy as f32 * factor) as i64))
+        } else {
+            (image, (hx as i64, hy as i64))
+        };
+
+        imageops::overlay(&mut screen.rgba_image, &image, px as i64 - hotspot.0, py as i64 - hotspot.1);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use crate::canonical::ScreenInfo;
+    use super::*;
+
+    #[test]
+    fn draw_cursor_test() {
+        let white = Rgba([255, 255, 255, 255]);
+        let screens = vec![
+            ScreenInfo { name: "left".into(), is_primary: true, xywh: (0, 0, 100, 100), sf: 1.0, rgba_image: RgbaImage::from_pixel(100, 100, white) },
+            ScreenInfo { name: "right".into(), is_primary: false, xywh: (100, 0, 100, 100), sf: 2.0, rgba_image: RgbaImage::from_pixel(100, 100, white) },
+        ];
+        let arrow = |x: f32, y: f32| Cursor { position: PhysicalPos { x, y }, image: None };
+        let black = |snapshot: &Snapshot, screen: usize, x: u32, y: u32| snapshot.screens[screen].rgba_image.get_pixel(x, y).0 == [0, 0, 0, 255];
+
+        // the arrow follows the scale factor of the screen under it
+        let mut snapshot = Snapshot::new(screens.clone(), vec![]).unwrap();
+        snapshot.draw_cursor(&arrow(10.0, 10.0), CursorScale::Auto).unwrap();
+        snapshot.draw_cursor(&arrow(110.0, 10.0), CursorScale::Auto).unwrap();
+        assert!(black(&snapshot, 0, 10, 10) && black(&snapshot, 0, 10, 26) && !black(&snapshot, 0, 10, 27));
+        assert!(black(&snapshot, 1, 10, 10) && black(&snapshot, 1, 11, 43) && !black(&snapshot, 1, 10, 44));
+        assert_eq!(snapshot.screens[0].rgba_image.get_pixel(9, 10).0, [255, 255, 255, 255]);
+
+        // the image of the backend is put by its hotspot, and cut at the edges
+        let mut snapshot = Snapshot::new(screens.clone(), vec![]).unwrap();
+        let image = RgbaImage::from_pixel(4, 4, Rgba([255, 0, 0, 255]));
+        let cursor = Cursor { position: PhysicalPos { x: 1.0, y: 98.0 }, image: Some((image, (2, 2))) };
+        snapshot.draw_cursor(&cursor, CursorScale::Auto).unwrap();
+        let red = |x, y| snapshot.screens[0].rgba_image.get_pixel(x, y).0 == [255, 0, 0, 255];
+        assert!(red(0, 96) && red(2, 99) && !red(3, 96) && !red(0, 95));
+
+        // a fixed scale for both
+        let mut snapshot = Snapshot::new(screens, vec![]).unwrap();
+        snapshot.draw_cursor(&cursor, CursorScale::Fixed(2.0)).unwrap();
+        snapshot.draw_cursor(&arrow(150.0, 50.0), CursorScale::Fixed(1.0)).unwrap();
+        let red = |x, y| snapshot.screens[0].rgba_image.get_pixel(x, y).0 == [255, 0, 0, 255];
+        assert!(red(0, 94) && !red(0, 93) && red(4, 99) && !red(5, 99));
+        assert!(black(&snapshot, 1, 50, 66) && !black(&snapshot, 1, 50, 67));
+
+        // off the screens
+        let before = snapshot.screens[0].rgba_image.clone();
+        snapshot.draw_cursor(&arrow(-5.0, 0.0), CursorScale::Auto).unwrap();
+        assert_eq!(snapshot.screens[0].rgba_image, before);
+
+        // a factor which is not positive is refused, not ignored
+        for factor in [0.0, -1.0, f32::NAN] {
+            let result = snapshot.draw_cursor(&arrow(10.0, 10.0), CursorScale::Fixed(factor));
+            assert!(matches!(result, Err(CaptureError::InvalidRequest(_))));
+        }
+        assert_eq!(snapshot.screens[0].rgba_image, before);
+    }
+}
@@ -0,0 +1,79 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::io;
+use image::ImageError;
+
+/// Everything that can go wrong while capturing, cropping or saving.
+///
+/// More variants may be added in minor versions.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CaptureError {
+    /// the backend reports no monitor at all
+    NoMonitors,
+    /// the requested monitor or window does not exist (or is off-screen)
+    NotFound(String),
+    /// the capture backend fails, with the error it reports
+    Backend(Box<dyn Error + Send + Sync>),
+    /// the platform refuses to capture the screen (e.g. the screen recording permission on macOS)
+    PermissionDenied(String),
+    /// the request can not be run as given (e.g. a delay beyond the range of the clock)
+    InvalidRequest(String),
+    /// the cropper window can not be created or run
+    Ui(String),
+    /// an image can not be encoded or decoded
+    Encode(ImageError),
+    /// a file (image or archive) can not be read or written, or the archive is malformed
+    Io(io::Error),
+}
+
+impl Display for CaptureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaptureError::NoMonitors => write!(f, "no monitor found"),
+            CaptureError::NotFound(message) => write!(f, "{}", message),
+            CaptureError::Backend(err) => write!(f, "capture failed: {}", err),
+            CaptureError::PermissionDenied(message) => write!(f, "permission denied: {}", message),
+            CaptureError::InvalidRequest(message) => write!(f, "invalid request: {}", message),
+            CaptureError::Ui(message) => write!(f, "ui failed: {}", message),
+            CaptureError::Encode(err) => write!(f, "{}", err),
+            CaptureError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for CaptureError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            CaptureError::Backend(err) => Some(err.as_ref()),
+            CaptureError::Encode(err) => Some(err),
+            CaptureError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CaptureError {
+    fn from(value: io::Error) -> Self {
+        CaptureError::Io(value)
+    }
+}
+
+impl From<ImageError> for CaptureError {
+    fn from(value: ImageError) -> Self {
+        CaptureError::Encode(value)
+    }
+}
+
+impl From<serde_json::Error> for CaptureError {
+    fn from(value: serde_json::Error) -> Self {
+        CaptureError::Io(value.into())
+    }
+}
+
+impl CaptureError {
+    /// A malformed file.
+    pub(crate) fn invalid_data(message: impl Into<String>) -> CaptureError {
+        CaptureError::Io(io::Error::new(io::ErrorKind::InvalidData, message.into()))
+    }
+}
@@ -1,7 +1,23 @@
+mod archive;
+mod compose;
+mod coords;
+mod cursor;
+mod error;
+mod redact;
+mod region;
 mod snapshot;
+mod stacking;
 
+pub use archive::ARCHIVE_EXTENSION;
+pub use compose::ScalePolicy;
+pub use coords::{CoordinateSpace, DesktopSpace, LogicalPos, LogicalRect, PhysicalPos, PhysicalRect};
+pub use cursor::{Cursor, CursorScale};
+pub use error::CaptureError;
+pub use redact::Redaction;
+pub use region::intersect;
 pub use snapshot::{AppInfo, ScreenInfo, Snapshot};
 
 /// x, y (top-left corner) and width, height
+#[allow(clippy::upper_case_acronyms)]
 pub type XYWH = (i32, i32, u32, u32);
 
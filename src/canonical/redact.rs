@@ -0,0 +1,162 @@
+use image::{imageops, Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use crate::canonical::{intersect, Snapshot, XYWH};
+
+/// How to hide the pixels of an area. The original pixels are replaced, they can not be recovered from the result.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Redaction {
+    /// average the pixels of each block, whose side length is in pixels, at least [`Redaction::MIN_BLOCK`]
+    Pixelate { block: u32 },
+    /// gaussian blur, only the pixels inside the area are mixed, with a sigma (in pixels) of at least [`Redaction::MIN_SIGMA`]
+    Blur { sigma: f32 },
+    /// fill with opaque black
+    Blackout,
+}
+
+impl Redaction {
+    /// the smallest block of pixelate, smaller ones are raised to it, since a block of one pixel hides nothing
+    pub const MIN_BLOCK: u32 = 2;
+    /// the smallest sigma of blur, smaller ones (and invalid ones) are raised to it, since a narrower blur leaves the pixels readable
+    pub const MIN_SIGMA: f32 = 2.0;
+
+    /// Redact the area (in pixels of the image, clipped to it) of the image.
+    pub fn apply(self, image: &mut RgbaImage, area: XYWH) {
+        let Some((x, y, w, h)) = intersect(area, (0, 0, image.width(), image.height())) else {
+            return;
+        };
+        let (x, y) = (x as u32, y as u32);
+
+        match self {
+            Redaction::Pixelate { block } => {
+                let block = block.max(Redaction::MIN_BLOCK);
+                for by in (y..y + h).step_by(block as usize) {
+                    for bx in (x..x + w).step_by(block as usize) {
+                        let (bw, bh) = (block.min(x + w - bx), block.min(y + h - by));
+                        let mut sum = [0u64; 4];
+                        for py in by..by + bh {
+                            for px in bx..bx + bw {
+                                for (s, c) in sum.iter_mut().zip(image.get_pixel(px, py).0) {
+                                    *s += c as u64;
+                                }
+                            }
+                        }
+                        let count = (bw * bh) as u64;
+                        let average = Rgba(sum.map(|s| ((s + count / 2) / count) as u8));
+                        for py in by..by + bh {
+                            for px in bx..bx + bw {
+                                image.put_pixel(px, py, average);
+                            }
+                        }
+                    }
+                }
+            }
+            Redaction::Blur { sigma } => {
+                let sigma = if sigma.is_finite() { sigma.max(Redaction::MIN_SIGMA) } else { Redaction::MIN_SIGMA };
+                let blurred = imageops::blur(&*imageops::crop_imm(image, x, y, w, h), sigma);
+                imageops::replace(image, &blurred, x as i64, y as i64);
+            }
+            Redaction::Blackout => {
+                for py in y..y + h {
+                    for px in x..x + w {
+                        image.put_pixel(px, py, Rgba([0, 0, 0, 255]));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Snapshot {
+    /// Redact the region (in desktop coordinates) on the native pixels of every screen it covers.
+    pub fn redact(&mut self, xywh: XYWH, redaction: Redaction) {
+        for screen in &mut self.screens {
+            let Some((x, y, w, h)) = intersect(screen.xywh, xywh) else {
+                continue;
+            };
+            let (sx, sy, _, _) = screen.xywh;
+            let (dx, dy) = screen.density();
+
+            // whole pixels of the image covering the region
+            let ix1 = ((x - sx) as f32 * dx).floor() as i32;
+            let iy1 = ((y - sy) as f32 * dy).floor() as i32;
+            let ix2 = ((x + w as i32 - sx) as f32 * dx).ceil() as i32;
+            let iy2 = ((y + h as i32 - sy) as f32 * dy).ceil() as i32;
+            redaction.apply(&mut screen.rgba_image, (ix1, iy1, (ix2 - ix1) as u32, (iy2 - iy1) as u32));
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use crate::canonical::ScreenInfo;
+    use super::*;
+
+    fn gradient(w: u32, h: u32) -> RgbaImage {
+        RgbaImage::from_fn(w, h, |x, y| Rgba([x as u8 * 10, y as u8 * 10, 0, 255]))
+    }
+
+    #[test]
+    fn redaction_test() {
+        // blocks start at the corner of the area, the last ones are cut short
+        let mut image = gradient(10, 10);
+        Redaction::Pixelate { block: 4 }.apply(&mut image, (1, 1, 6, 4));
+        assert_eq!(image.get_pixel(1, 1).0, [25, 25, 0, 255]);
+        assert_eq!(image.get_pixel(4, 4).0, [25, 25, 0, 255]);
+        assert_eq!(image.get_pixel(5, 1).0, [55, 25, 0, 255]);
+        assert_eq!(image.get_pixel(6, 4).0, [55, 25, 0, 255]);
+        assert_eq!(image.get_pixel(7, 1).0, [70, 10, 0, 255]);
+        assert_eq!(image.get_pixel(1, 5).0, [10, 50, 0, 255]);
+
+        // a block of one pixel would keep the pixels as they are
+        let mut image = gradient(10, 10);
+        Redaction::Pixelate { block: 1 }.apply(&mut image, (0, 0, 2, 2));
+        assert_eq!(image.get_pixel(0, 0).0, [5, 5, 0, 255]);
+        assert_eq!(image.get_pixel(1, 1).0, [5, 5, 0, 255]);
+
+        // clipped to the image
+        let mut image = gradient(10, 10);
+        Redaction::Blackout.apply(&mut image, (-5, 8, 10, 10));
+        assert_eq!(image.get_pixel(0, 9).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(4, 8).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(5, 8).0, [50, 80, 0, 255]);
+        Redaction::Blackout.apply(&mut image, (20, 20, 5, 5));
+
+        // the pixels outside the area are not mixed in
+        let mut image = RgbaImage::from_fn(10, 10, |x, _| if x < 5 { Rgba([0, 0, 0, 255]) } else { Rgba([200, 200, 200, 255]) });
+        Redaction::Blur { sigma: 3.0 }.apply(&mut image, (5, 0, 5, 10));
+        assert_eq!(image.get_pixel(4, 5).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(5, 5).0, [200, 200, 200, 255]);
+        let mut image = gradient(10, 10);
+        Redaction::Blur { sigma: 3.0 }.apply(&mut image, (0, 0, 10, 10));
+        assert_ne!(image.get_pixel(0, 0).0, [0, 0, 0, 255]);
+
+        // a small sigma would keep the pixels as they are
+        let checker = RgbaImage::from_fn(10, 10, |x, y| if (x + y) % 2 == 0 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) });
+        for sigma in [0.0, 0.3, -1.0, f32::NAN, f32::INFINITY] {
+            let mut image = checker.clone();
+            Redaction::Blur { sigma }.apply(&mut image, (0, 0, 10, 10));
+            let changed = image.pixels().zip(checker.pixels()).filter(|(a, b)| a.0[0].abs_diff(b.0[0]) > 64).count();
+            assert_eq!(changed, 100, "sigma {}", sigma);
+        }
+    }
+
+    #[test]
+    fn snapshot_redact_test() {
+        let mut snapshot = Snapshot::new(
+            vec![
+                ScreenInfo { name: "left".into(), is_primary: true, xywh: (0, 0, 10, 10), sf: 1.0, rgba_image: gradient(10, 10) },
+                // in points, with 2x pixels
+                ScreenInfo { name: "right".into(), is_primary: false, xywh: (10, 0, 10, 10), sf: 2.0, rgba_image: gradient(20, 20) },
+            ],
+            vec![],
+        ).unwrap();
+
+        snapshot.redact((8, 2, 4, 2), Redaction::Blackout);
+        let black = |screen: usize, x: u32, y: u32| snapshot.screens[screen].rgba_image.get_pixel(x, y).0 == [0, 0, 0, 255];
+        assert!(black(0, 8, 2) && black(0, 9, 3));
+        assert!(!black(0, 7, 2) && !black(0, 8, 4));
+        assert!(black(1, 0, 4) && black(1, 3, 7));
+        assert!(!black(1, 4, 4) && !black(1, 0, 8));
+    }
+}
@@ -0,0 +1,170 @@
+use image::{Rgba, RgbaImage};
+use crate::canonical::{ScreenInfo, Snapshot, XYWH};
+
+/// Intersection of two boxes, `None` if they do not overlap.
+pub fn intersect(a: XYWH, b: XYWH) -> Option<XYWH> {
+    let x1 = a.0.max(b.0);
+    let y1 = a.1.max(b.1);
+    let x2 = (a.0 + a.2 as i32).min(b.0 + b.2 as i32);
+    let y2 = (a.1 + a.3 as i32).min(b.1 + b.3 as i32);
+
+    if x1 < x2 && y1 < y2 {
+        Some((x1, y1, (x2 - x1) as u32, (y2 - y1) as u32))
+    } else {
+        None
+    }
+}
+
+impl ScreenInfo {
+    /// Copy the part of the screen inside `area` (in desktop coordinates) into `canvas`,
+    /// whose top-left corner is at `origin` (in desktop coordinates) and which has `scale`
+    /// pixels per desktop unit.
+    ///
+    /// When the image has more (or less) pixels than the canvas, it is sampled with the nearest pixel.
+    fn paint_onto(&self, canvas: &mut RgbaImage, origin: (i32, i32), area: XYWH, scale: f64) {
+        let Some((x, y, w, h)) = intersect(self.xywh, area) else {
+            return;
+        };
+        let (sx, sy, sw, sh) = self.xywh;
+        let (iw, ih) = self.rgba_image.dimensions();
+
+        // range of the canvas pixels covered by the intersection
+        let cx1 = ((x - origin.0) as f64 * scale).round() as u32;
+        let cy1 = ((y - origin.1) as f64 * scale).round() as u32;
+        let cx2 = (((x + w as i32 - origin.0) as f64 * scale).round() as u32).min(canvas.width());
+        let cy2 = (((y + h as i32 - origin.1) as f64 * scale).round() as u32).min(canvas.height());
+
+        for cy in cy1..cy2 {
+            let gy = origin.1 as f64 + cy as f64 / scale - sy as f64;
+            let iy = ((gy * ih as f64 / sh as f64) as u32).min(ih - 1);
+            for cx in cx1..cx2 {
+                let gx = origin.0 as f64 + cx as f64 / scale - sx as f64;
+                let ix = ((gx * iw as f64 / sw as f64) as u32).min(iw - 1);
+                canvas.put_pixel(cx, cy, *self.rgba_image.get_pixel(ix, iy));
+            }
+        }
+    }
+}
+
+impl Snapshot {
+    /// Crop the region (in desktop coordinates) out of the screens, the area not covered
+    /// by any screen is transparent.
+    pub fn crop(&self, xywh: XYWH) -> RgbaImage {
+        self.crop_with_fill(xywh, [0, 0, 0, 0])
+    }
+
+    /// Same as [`Snapshot::crop`], but the area not covered by any screen is filled with
+    /// the given color (in RGBA format).
+    ///
+    /// The region may span several screens, they are painted in order.
+    pub fn crop_with_fill(&self, xywh: XYWH, fill: [u8; 4]) -> RgbaImage {
+        self.crop_scaled(xywh, fill, 1.0)
+    }
+
+    /// Same as [`Snapshot::crop`], but keep every captured pixel: the result has the largest density
+    /// of the screens under the region, which differs from the region size when a backend reports
+    /// the screens in other units than pixels (e.g. points on macOS).
+    pub fn crop_native(&self, xywh: XYWH) -> RgbaImage {
+        self.crop_scaled(xywh, [0, 0, 0, 0], self.native_scale(xywh))
+    }
+
+    /// The size of the image [`Snapshot::crop_native`] gives for the region, in pixels.
+    pub fn native_size(&self, xywh: XYWH) -> (u32, u32) {
+        scaled_size(xywh, self.native_scale(xywh))
+    }
+
+    fn native_scale(&self, xywh: XYWH) -> f64 {
+        self.screens.iter()
+            .filter(|screen| intersect(screen.xywh, xywh).is_some())
+            .map(|screen| screen.density().0 as f64)
+            .fold(1.0, f64::max)
+    }
+
+    fn crop_scaled(&self, xywh: XYWH, fill: [u8; 4], scale: f64) -> RgbaImage {
+        let (x, y, _, _) = xywh;
+        let (width, height) = scaled_size(xywh, scale);
+        let mut canvas = RgbaImage::from_pixel(width, height, Rgba(fill));
+        for screen in &self.screens {
+            if screen.rgba_image.width() > 0 && screen.rgba_image.height() > 0 {
+                screen.paint_onto(&mut canvas, (x, y), xywh, scale);
+            }
+        }
+        canvas
+    }
+}
+
+fn scaled_size((_, _, w, h): XYWH, scale: f64) -> (u32, u32) {
+    ((w as f64 * scale).round() as u32, (h as f64 * scale).round() as u32)
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+
+    fn screen(xywh: XYWH, scale: u32, color: [u8; 4]) -> ScreenInfo {
+        ScreenInfo {
+            name: format!("{:?}", xywh),
+            is_primary: false,
+            xywh,
+            sf: scale as f32,
+            rgba_image: RgbaImage::from_pixel(xywh.2 * scale, xywh.3 * scale, Rgba(color)),
+        }
+    }
+
+    #[test]
+    fn crop_across_screens_test() {
+        // [left, 2x density] [gap] [right]
+        //                    [      bottom      ]
+        let snapshot = Snapshot::new(
+            vec![
+                screen((-20, 0, 20, 10), 2, [255, 0, 0, 255]),
+                screen((10, 0, 10, 10), 1, [0, 255, 0, 255]),
+                screen((-20, 10, 40, 10), 1, [0, 0, 255, 255]),
+            ],
+            vec![],
+        ).unwrap();
+        assert_eq!(snapshot.xywh, (-20, 0, 40, 20));
+
+        let fill = [9, 9, 9, 9];
+        let image = snapshot.crop_with_fill((-5, 5, 20, 10), fill);
+        assert_eq!(image.dimensions(), (20, 10));
+        assert_eq!(image.get_pixel(0, 0).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(4, 4).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(5, 0).0, fill);
+        assert_eq!(image.get_pixel(14, 4).0, fill);
+        assert_eq!(image.get_pixel(15, 0).0, [0, 255, 0, 255]);
+        assert_eq!(image.get_pixel(0, 5).0, [0, 0, 255, 255]);
+        assert_eq!(image.get_pixel(19, 9).0, [0, 0, 255, 255]);
+
+        // entirely outside of the desktop
+        let image = snapshot.crop((100, 100, 3, 3));
+        assert!(image.pixels().all(|p| p.0 == [0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn crop_native_test() {
+        // a screen whose image has 2x pixels, with a distinct color on each pixel
+        let mut left = screen((0, 0, 4, 4), 2, [0, 0, 0, 255]);
+        left.rgba_image = RgbaImage::from_fn(8, 8, |x, y| Rgba([x as u8, y as u8, 0, 255]));
+        let snapshot = Snapshot::new(vec![left, screen((4, 0, 4, 4), 1, [0, 0, 255, 255])], vec![]).unwrap();
+
+        // every pixel of the image is kept
+        let image = snapshot.crop_native((1, 1, 2, 2));
+        assert_eq!(image.dimensions(), (4, 4));
+        assert_eq!(snapshot.native_size((1, 1, 2, 2)), (4, 4));
+        for (x, y, pixel) in image.enumerate_pixels() {
+            assert_eq!(pixel.0, [x as u8 + 2, y as u8 + 2, 0, 255]);
+        }
+
+        // the 1x screen is upscaled next to it
+        let image = snapshot.crop_native((3, 0, 2, 1));
+        assert_eq!(image.dimensions(), (4, 2));
+        assert_eq!(image.get_pixel(1, 1).0, [7, 1, 0, 255]);
+        assert_eq!(image.get_pixel(2, 0).0, [0, 0, 255, 255]);
+        assert_eq!(image.get_pixel(3, 1).0, [0, 0, 255, 255]);
+
+        // the same as a plain crop on 1x screens
+        assert_eq!(snapshot.crop_native((5, 1, 2, 2)), snapshot.crop((5, 1, 2, 2)));
+        assert_eq!(snapshot.native_size((5, 1, 2, 2)), (2, 2));
+    }
+}
@@ -1,16 +1,20 @@
 use std::fmt::{Debug, Formatter};
 use std::io::Cursor;
 use image::{ImageFormat, RgbaImage};
-use crate::canonical::XYWH;
+use serde::{Deserialize, Serialize};
+use crate::canonical::{CaptureError, XYWH};
 
 /// Although all fields are public, it is recommended not to modify them directly
-#[allow(unused)]
+///
+/// Only the metadata is serialized, the image is skipped.
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ScreenInfo {
     pub name: String,
     pub is_primary: bool,
     /// The bounding box of the screen
     pub xywh: XYWH,
     pub sf: f32,
+    #[serde(skip)]
     pub rgba_image: RgbaImage,
 }
 
@@ -49,33 +53,72 @@ impl ScreenInfo {
     }
 }
 
-#[allow(unused)]
+/// Info of a window.
+///
+/// The fields added after the first release default to empty values when they are missing
+/// (e.g. in an old archive), then the derived ones are filled in by [`Snapshot::new`].
+#[derive(Clone, Default, Serialize, Deserialize)]
 pub struct AppInfo {
     pub name: String,
     pub title: String,
     pub is_minimized: bool,
     pub xywh: XYWH,
+    /// The stacking order, 0 is the topmost, `None` if the backend can not tell
+    #[serde(default)]
+    pub z: Option<u32>,
+    /// The id of the window given by the platform
+    #[serde(default)]
+    pub id: u32,
+    /// The id of the owner process, `None` if the backend does not know it
+    /// (always on macOS, and on X11 when the window has no `_NET_WM_PID`)
+    #[serde(default)]
+    pub pid: Option<u32>,
+    /// The name of the screen the window (mostly) lives on, empty if it is off-screen
+    #[serde(default)]
+    pub monitor: String,
+    /// Whether the window is entirely covered by the (not minimized) windows above it,
+    /// always `false` when the stacking order is not known
+    #[serde(default)]
+    pub is_occluded: bool,
+    /// Whether the window is entirely outside of all screens
+    #[serde(default)]
+    pub is_off_screen: bool,
+    /// The pixels of the window itself, only when it is captured on purpose (see `Snapper::capture_app`)
+    #[serde(skip)]
+    pub rgba_image: Option<RgbaImage>,
 }
 
 impl Debug for AppInfo {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "{}: title = \"{}\", minimize = {}, position = ({},{}), size = {}x{}",
+            "{}: title = \"{}\", minimize = {}, position = ({},{}), size = {}x{}, z = {}, id = {}, monitor = \"{}\"",
             self.name,
             self.title,
             self.is_minimized,
             self.xywh.0,
             self.xywh.1,
             self.xywh.2,
-            self.xywh.3
-        )
+            self.xywh.3,
+            self.z.map_or("?".into(), |z| z.to_string()),
+            self.id,
+            self.monitor
+        )?;
+        if let Some(pid) = self.pid {
+            write!(f, ", pid = {}", pid)?;
+        }
+        if self.is_occluded {
+            write!(f, ", occluded")?;
+        }
+        if self.is_off_screen {
+            write!(f, ", off-screen")?;
+        }
+        Ok(())
     }
 }
 
 /// A snapshot of the current state of the monitor(s) and the app(s).
-#[allow(unused)]
-#[derive(Debug)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Snapshot {
     /// The bounding box of the snapshot (which includes all screens and apps)
     pub xywh: XYWH,
@@ -83,11 +126,11 @@ pub struct Snapshot {
     pub apps: Vec<AppInfo>,
 }
 
-#[allow(unused)]
 impl Snapshot {
-    pub fn new(screens: Vec<ScreenInfo>, apps: Vec<AppInfo>) -> Snapshot {
+    /// Build the snapshot, fails with [`CaptureError::NoMonitors`] if there is no screen.
+    pub fn new(screens: Vec<ScreenInfo>, apps: Vec<AppInfo>) -> Result<Snapshot, CaptureError> {
         if screens.is_empty() {
-            panic!("No screen found");
+            return Err(CaptureError::NoMonitors);
         }
 
         let (x1, y1, x2, y2) = screens.iter().fold(
@@ -104,6 +147,8 @@ impl Snapshot {
         );
         let xywh: XYWH = (x1, y1, (x2 - x1) as u32, (y2 - y1) as u32);
 
-        Snapshot { xywh, screens, apps }
+        let mut snapshot = Snapshot { xywh, screens, apps };
+        snapshot.resolve_stacking();
+        Ok(snapshot)
     }
 }
\ No newline at end of file
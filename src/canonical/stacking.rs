@@ -0,0 +1,146 @@
+use crate::canonical::{intersect, AppInfo, PhysicalPos, PhysicalRect, Snapshot, XYWH};
+
+/// Whether the union of `covers` contains every pixel of `target`.
+fn is_covered(target: XYWH, covers: &[XYWH]) -> bool {
+    let covers: Vec<XYWH> = covers.iter().filter_map(|&c| intersect(target, c)).collect();
+    if covers.is_empty() {
+        return false;
+    }
+
+    // split the target into cells by the edges of the covers, every cell must be covered
+    let edges = |start: fn(&XYWH) -> i32, len: fn(&XYWH) -> u32| {
+        let mut edges: Vec<i32> = covers.iter()
+            .flat_map(|c| [start(c), start(c) + len(c) as i32])
+            .chain([start(&target), start(&target) + len(&target) as i32])
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        edges
+    };
+    let xs = edges(|c| c.0, |c| c.2);
+    let ys = edges(|c| c.1, |c| c.3);
+
+    xs.windows(2).all(|x| ys.windows(2).all(|y| {
+        covers.iter().any(|c| c.0 <= x[0] && x[1] <= c.0 + c.2 as i32 && c.1 <= y[0] && y[1] <= c.1 + c.3 as i32)
+    }))
+}
+
+impl Snapshot {
+    /// Sort the apps from the topmost to the bottommost, then derive the monitor and the visibility of each one.
+    ///
+    /// The apps whose stacking order is not known are kept in their order after the others, and never occluded.
+    pub(super) fn resolve_stacking(&mut self) {
+        self.apps.sort_by_key(|app| (app.z.is_none(), app.z));
+
+        for index in 0..self.apps.len() {
+            let xywh = self.apps[index].xywh;
+
+            // the screen with the largest overlap
+            let screen = self.screens.iter()
+                .filter_map(|screen| intersect(screen.xywh, xywh).map(|(_, _, w, h)| (w as u64 * h as u64, screen)))
+                .max_by_key(|(area, _)| *area)
+                .map(|(_, screen)| screen);
+
+            let above: Vec<XYWH> = self.apps[..index].iter()
+                .filter(|app| !app.is_minimized)
+                .map(|app| app.xywh)
+                .collect();
+
+            let app = &mut self.apps[index];
+            app.is_off_screen = screen.is_none();
+            if app.monitor.is_empty() {
+                app.monitor = screen.map(|screen| screen.name.clone()).unwrap_or_default();
+            }
+            app.is_occluded = app.z.is_some() && !app.is_off_screen && is_covered(xywh, &above);
+        }
+    }
+
+    /// The topmost window at the point, minimized windows are skipped.
+    pub fn app_at(&self, pos: PhysicalPos) -> Option<&AppInfo> {
+        self.apps.iter()
+            .filter(|app| !app.is_minimized && !app.is_off_screen)
+            .find(|app| {
+                let rect = PhysicalRect::from_xywh(app.xywh);
+                pos.x >= rect.min.x && pos.y >= rect.min.y && pos.x < rect.max.x && pos.y < rect.max.y
+            })
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use image::RgbaImage;
+    use crate::canonical::ScreenInfo;
+    use super::*;
+
+    fn app(name: &str, z: u32, xywh: XYWH) -> AppInfo {
+        AppInfo {
+            name: name.into(),
+            title: name.into(),
+            xywh,
+            z: Some(z),
+            id: z + 100,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn is_covered_test() {
+        assert!(is_covered((0, 0, 10, 10), &[(0, 0, 10, 5), (-5, 5, 20, 5)]));
+        assert!(!is_covered((0, 0, 10, 10), &[(0, 0, 10, 5), (0, 5, 9, 5)]));
+        assert!(!is_covered((0, 0, 10, 10), &[(20, 20, 10, 10)]));
+        assert!(!is_covered((0, 0, 10, 10), &[]));
+    }
+
+    #[test]
+    fn resolve_stacking_test() {
+        let screen = |name: &str, xywh: XYWH| ScreenInfo {
+            name: name.into(),
+            is_primary: false,
+            xywh,
+            sf: 1.0,
+            rgba_image: RgbaImage::new(xywh.2, xywh.3),
+        };
+        let mut minimized = app("minimized", 0, (0, 0, 100, 100));
+        minimized.is_minimized = true;
+        // given in any order
+        let snapshot = Snapshot::new(
+            vec![screen("left", (0, 0, 100, 100)), screen("right", (100, 0, 100, 100))],
+            vec![
+                app("bottom", 4, (10, 10, 50, 50)),
+                app("away", 3, (500, 500, 10, 10)),
+                app("middle", 2, (90, 0, 60, 60)),
+                app("top", 1, (0, 0, 95, 95)),
+                minimized,
+            ],
+        ).unwrap();
+
+        let names: Vec<&str> = snapshot.apps.iter().map(|app| app.name.as_str()).collect();
+        assert_eq!(names, ["minimized", "top", "middle", "away", "bottom"]);
+
+        let flags: Vec<(&str, bool, bool)> = snapshot.apps.iter()
+            .map(|app| (app.monitor.as_str(), app.is_occluded, app.is_off_screen))
+            .collect();
+        assert_eq!(flags, [
+            ("left", false, false),
+            ("left", false, false),
+            ("right", false, false),
+            ("", false, true),
+            // covered by 'top', the minimized window does not count
+            ("left", true, false),
+        ]);
+
+        // the topmost visible window
+        assert_eq!(snapshot.app_at(PhysicalPos { x: 92.0, y: 10.0 }).unwrap().name, "top");
+        assert_eq!(snapshot.app_at(PhysicalPos { x: 96.0, y: 10.0 }).unwrap().name, "middle");
+        assert!(snapshot.app_at(PhysicalPos { x: 96.0, y: 96.0 }).is_none());
+
+        // without the stacking order, the order of the backend is kept and nothing is occluded
+        let unknown = |name: &str, xywh: XYWH| AppInfo { z: None, ..app(name, 0, xywh) };
+        let snapshot = Snapshot::new(
+            vec![screen("left", (0, 0, 100, 100))],
+            vec![unknown("first", (0, 0, 100, 100)), app("known", 1, (0, 0, 100, 100)), unknown("second", (10, 10, 10, 10))],
+        ).unwrap();
+        let flags: Vec<(&str, bool)> = snapshot.apps.iter().map(|app| (app.name.as_str(), app.is_occluded)).collect();
+        assert_eq!(flags, [("known", false), ("first", false), ("second", false)]);
+    }
+}
@@ -0,0 +1,561 @@
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use clap::{Parser, Subcommand, ValueEnum};
+use capture::{
+    AppInfo, AppSelector, Capture, CaptureBackend, CaptureError, CaptureRequest, CaptureTarget, CropperConfig, CursorScale,
+    NamingTemplate, Output, OutputFormat, PhysicalPos, PrivacyFilter, Redaction, ScalePolicy, SeriesError, Snapper, Snapshot,
+    SyntheticBackend, XCapBackend, ARCHIVE_EXTENSION,
+};
+
+/// exit codes of the command line interface
+pub mod exit_code {
+    pub const SUCCESS: u8 = 0;
+    /// the screens or windows can not be captured
+    pub const CAPTURE_FAILED: u8 = 1;
+    /// bad arguments
+    pub const USAGE: u8 = 2;
+    /// the requested monitor or window does not exist
+    pub const NOT_FOUND: u8 = 3;
+    /// the interactive crop is cancelled by the user
+    pub const CANCELLED: u8 = 4;
+    /// the result can not be written
+    pub const WRITE_FAILED: u8 = 5;
+    /// the platform refuses to capture the screen
+    pub const PERMISSION_DENIED: u8 = 6;
+}
+
+#[derive(Parser, Debug)]
+#[command(name = "capture", version, about = "Take screenshots of the screens, monitors and windows")]
+pub struct Cli {
+    /// where to write the captured image, with tokens {date} {time} {monitor} {app} {title} {w} {h} {n}
+    #[arg(short, long, global = true, default_value = "capture-{date}-{time}")]
+    output: String,
+
+    /// image format, guessed from the extension of the output when omitted (png by default)
+    #[arg(short, long, global = true, value_enum)]
+    format: Option<Format>,
+
+    /// quality of jpeg, 1 ~ 100
+    #[arg(short, long, global = true, default_value_t = OutputFormat::DEFAULT_JPEG_QUALITY, value_parser = clap::value_parser!(u8).range(1..=100))]
+    quality: u8,
+
+    /// replace the existing file instead of picking a free name
+    #[arg(long, global = true)]
+    overwrite: bool,
+
+    /// use the snapshot archive instead of the live screens
+    #[arg(long, global = true, value_name = "ARCHIVE")]
+    load: Option<PathBuf>,
+
+    /// hide the windows matching the rules of the JSON file, like {"rules": [{"pattern": "chat", "redaction": {"blur": {"sigma": 12.0}}}]}
+    #[arg(long, global = true, value_name = "FILE")]
+    privacy: Option<PathBuf>,
+
+    /// black out the windows whose title or app name contains the pattern (case-insensitive), can be repeated
+    #[arg(long, global = true, value_name = "PATTERN")]
+    hide: Vec<String>,
+
+    /// include the mouse cursor
+    #[arg(long, global = true)]
+    cursor: bool,
+
+    /// size of the cursor, e.g. 2 on a 200% screen; when omitted, the cursor image of the system keeps
+    /// its own size and the built-in arrow (used when there is no such image) follows the scale factor of the screen
+    #[arg(long, global = true, value_name = "FACTOR", requires = "cursor", value_parser = parse_factor)]
+    cursor_scale: Option<f32>,
+
+    /// wait before capturing, in seconds
+    #[arg(long, global = true, value_name = "SECONDS", value_parser = parse_seconds)]
+    delay: Option<Duration>,
+
+    /// print the seconds left of the delay
+    #[arg(long, global = true, requires = "delay")]
+    countdown: bool,
+
+    /// capture repeatedly, every so many seconds, see '--shots'
+    #[arg(long, global = true, value_name = "SECONDS", value_parser = parse_seconds, requires = "shots")]
+    every: Option<Duration>,
+
+    /// number of captures with '--every'
+    #[arg(long, global = true, requires = "every", value_parser = clap::value_parser!(u32).range(1..))]
+    shots: Option<u32>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Crop a region interactively (default)
+    Region {
+        /// bound the application window under the mouse
+        #[arg(long)]
+        auto_bounding: bool,
+        /// how far (in points) from an edge of the selection the mouse still grabs it
+        #[arg(long, value_name = "POINTS", value_parser = parse_points)]
+        edge_tolerance: Option<f32>,
+        /// the smallest selection, in physical pixels
+        #[arg(long, value_name = "WxH", value_parser = parse_size)]
+        min_size: Option<[u32; 2]>,
+        /// hide the magnifier next to the mouse
+        #[arg(long)]
+        no_magnifier: bool,
+    },
+    /// Capture a region of the desktop without any UI
+    Rect {
+        /// left, in desktop coordinates
+        #[arg(allow_negative_numbers = true)]
+        x: i32,
+        /// top, in desktop coordinates
+        #[arg(allow_negative_numbers = true)]
+        y: i32,
+        width: u32,
+        height: u32,
+        /// color of the area not covered by any monitor, RRGGBB or RRGGBBAA
+        #[arg(long, default_value = "00000000", value_parser = parse_color)]
+        fill: [u8; 4],
+    },
+    /// Capture the whole virtual desktop
+    Full {
+        /// how to treat the monitors of different scale factors
+        #[arg(long, value_enum, default_value_t = Scale::Native)]
+        scale: Scale,
+    },
+    /// Capture a single monitor
+    Monitor {
+        /// name or index (see `list-monitors`) of the monitor
+        target: String,
+    },
+    /// Capture a window, including the parts covered by other windows when the platform allows
+    Window {
+        /// (part of) the title or the app name of the window, case-insensitive
+        pattern: String,
+        /// treat the pattern as the window id (see `list-windows`)
+        #[arg(long)]
+        id: bool,
+    },
+    /// List the monitors
+    ListMonitors {
+        /// print as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// List the windows, from the topmost to the bottommost
+    ListWindows {
+        /// print as JSON
+        #[arg(long)]
+        json: bool,
+        /// only the topmost visible window at the point, in desktop coordinates
+        #[arg(long, num_args = 2, value_names = ["X", "Y"], allow_negative_numbers = true)]
+        at: Option<Vec<i32>>,
+    },
+    /// Print the metadata of the monitors and the windows as JSON
+    Info,
+    /// Save the screens and the windows to a snapshot archive, which can be used with '--load' later
+    Snapshot {
+        /// path of the archive, '.capsnap' is appended if there is no extension
+        path: PathBuf,
+    },
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, ValueEnum)]
+enum Format {
+    Png,
+    Jpeg,
+    Webp,
+    Bmp,
+    Tiff,
+    Qoi,
+}
+
+impl Format {
+    fn with_quality(self, quality: u8) -> OutputFormat {
+        match self {
+            Format::Png => OutputFormat::Png,
+            Format::Jpeg => OutputFormat::Jpeg(quality),
+            Format::Webp => OutputFormat::WebP,
+            Format::Bmp => OutputFormat::Bmp,
+            Format::Tiff => OutputFormat::Tiff,
+            Format::Qoi => OutputFormat::Qoi,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug, ValueEnum)]
+enum Scale {
+    /// keep the pixels of each monitor
+    Native,
+    /// upscale to the largest scale factor
+    Max,
+    /// downscale to the smallest scale factor
+    Min,
+}
+
+impl From<Scale> for ScalePolicy {
+    fn from(value: Scale) -> Self {
+        match value {
+            Scale::Native => ScalePolicy::Native,
+            Scale::Max => ScalePolicy::UpscaleToMax,
+            Scale::Min => ScalePolicy::DownscaleToMin,
+        }
+    }
+}
+
+/// (exit code, message)
+type Failure = (u8, String);
+
+/// Parse the arguments and run the command, return the exit code of the process.
+pub fn run() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(err) => {
+            // '--help' and '--version' end up here as well
+            let _ = err.print();
+            return ExitCode::from(if err.use_stderr() { exit_code::USAGE } else { exit_code::SUCCESS });
+        }
+    };
+
+    match cli.exec() {
+        Ok(()) => ExitCode::from(exit_code::SUCCESS),
+        Err((code, message)) => {
+            eprintln!("error: {}", message);
+            ExitCode::from(code)
+        }
+    }
+}
+
+impl Cli {
+    fn exec(&self) -> Result<(), Failure> {
+        let default = Command::Region { auto_bounding: false, edge_tolerance: None, min_size: None, no_magnifier: false };
+        let request = match self.command.as_ref().unwrap_or(&default) {
+            Command::Region { auto_bounding, edge_tolerance, min_size, no_magnifier } => {
+                let default = CropperConfig::default();
+                CaptureRequest::new(CaptureTarget::Interactive).with_cropper_config(CropperConfig {
+                    auto_bounding: *auto_bounding,
+                    edge_tolerance: edge_tolerance.unwrap_or(default.edge_tolerance),
+                    min_size: min_size.unwrap_or(default.min_size),
+                    magnifier: !no_magnifier,
+                    ..default
+                })
+            }
+            Command::Rect { x, y, width, height, fill } => {
+                CaptureRequest::new(CaptureTarget::Region((*x, *y, *width, *height))).with_fill(*fill)
+            }
+            Command::Full { scale } => CaptureRequest::new(CaptureTarget::Full((*scale).into())),
+            Command::Monitor { target } => CaptureRequest::new(CaptureTarget::Monitor(target.clone())),
+            Command::Window { pattern, id } => {
+                let selector = match id {
+                    true => AppSelector::Id(pattern.parse()
+                        .map_err(|_| (exit_code::USAGE, format!("invalid window id '{}'", pattern)))?),
+                    false => AppSelector::Title(pattern.clone()),
+                };
+                CaptureRequest::new(CaptureTarget::Window(selector))
+            }
+            Command::ListMonitors { json } => return self.list_monitors(*json),
+            Command::ListWindows { json, at } => return self.list_windows(*json, at.as_deref()),
+            Command::Info => return print_json(&self.take_snapshot(true)?),
+            Command::Snapshot { path } => return self.save_snapshot(path),
+        };
+
+        let mut request = request.with_output(self.output())
+            .with_privacy(self.privacy()?)
+            .with_delay(self.delay.unwrap_or_default())
+            .with_cursor(self.cursor);
+        if let Some(factor) = self.cursor_scale {
+            request = request.with_cursor_scale(CursorScale::Fixed(factor));
+        }
+        if self.countdown {
+            request = request.with_countdown(|seconds| eprintln!("capturing in {}s", seconds));
+        }
+
+        let backend = self.backend()?;
+        let (captures, error) = match (self.every, self.shots) {
+            (Some(interval), Some(shots)) => match request.exec_series_with(backend.as_ref(), interval, shots) {
+                Ok(captures) => (captures, None),
+                // the frames written before the error are still reported
+                Err(SeriesError { captures, error }) => (captures, Some(error)),
+            },
+            _ => match request.exec_with(backend.as_ref()) {
+                Ok(capture) => (capture.into_iter().collect(), None),
+                Err(error) => (vec![], Some(error)),
+            },
+        };
+        self.finish(captures, error)
+    }
+
+    /// Print the paths of the captures, even the ones before an error, then tell how the captures end.
+    fn finish(&self, captures: Vec<Capture>, error: Option<CaptureError>) -> Result<(), Failure> {
+        if captures.is_empty() && error.is_none() {
+            return Err((exit_code::CANCELLED, "cancelled".to_string()));
+        }
+        for path in captures.into_iter().filter_map(|capture| capture.path) {
+            println!("{}", path.display());
+        }
+        match error {
+            Some(err) => Err(exec_failure(err, &self.output)),
+            None => Ok(()),
+        }
+    }
+
+    /// The monitors alone, their pixels are not captured.
+    fn list_monitors(&self, json: bool) -> Result<(), Failure> {
+        let screens = self.backend()?.monitors().map_err(capture_failure)?;
+        if screens.is_empty() {
+            return Err(capture_failure(CaptureError::NoMonitors));
+        }
+        if json {
+            print_json(&screens)
+        } else {
+            for (index, screen) in screens.iter().enumerate() {
+                println!("{}: {:?}", index, screen);
+            }
+            Ok(())
+        }
+    }
+
+    /// All windows, or the topmost visible one at the point.
+    fn list_windows(&self, json: bool, at: Option<&[i32]>) -> Result<(), Failure> {
+        let snapshot = self.take_snapshot(true)?;
+        let apps: Vec<&AppInfo> = match at {
+            Some(&[x, y]) => {
+                let app = snapshot.app_at(PhysicalPos { x: x as f32, y: y as f32 })
+                    .ok_or((exit_code::NOT_FOUND, format!("no window at ({}, {})", x, y)))?;
+                vec![app]
+            }
+            _ => snapshot.apps.iter().collect(),
+        };
+        if json {
+            print_json(&apps)
+        } else {
+            for app in apps {
+                println!("{:?}", app);
+            }
+            Ok(())
+        }
+    }
+
+    fn save_snapshot(&self, path: &Path) -> Result<(), Failure> {
+        let path = match path.extension() {
+            Some(_) => path.to_path_buf(),
+            None => path.with_extension(ARCHIVE_EXTENSION),
+        };
+        let snapshot = self.take_snapshot(true)?;
+        snapshot.save(&path)
+            .map_err(|err| (exit_code::WRITE_FAILED, format!("failed to write '{}': {}", path.display(), err)))?;
+        println!("{}", path.display());
+        Ok(())
+    }
+
+    /// The live screens, or the loaded archive.
+    fn backend(&self) -> Result<Box<dyn CaptureBackend>, Failure> {
+        match &self.load {
+            Some(path) => {
+                let snapshot = Snapshot::load(path)
+                    .map_err(|err| (exit_code::CAPTURE_FAILED, format!("failed to load '{}': {}", path.display(), err)))?;
+                Ok(Box::new(SyntheticBackend::from(snapshot)))
+            }
+            None => Ok(Box::new(XCapBackend)),
+        }
+    }
+
+    fn take_snapshot(&self, with_app_info: bool) -> Result<Snapshot, Failure> {
+        Snapper::take_snapshot_filtered(self.backend()?.as_ref(), with_app_info, &self.privacy()?).map_err(capture_failure)
+    }
+
+    /// The rules of the privacy file, followed by the hidden patterns.
+    fn privacy(&self) -> Result<PrivacyFilter, Failure> {
+        let mut filter = match &self.privacy {
+            Some(path) => PrivacyFilter::load(path)
+                .map_err(|err| (exit_code::USAGE, format!("failed to read '{}': {}", path.display(), err)))?,
+            None => PrivacyFilter::new(),
+        };
+        for pattern in &self.hide {
+            filter = filter.hide(pattern.as_str(), Redaction::Blackout);
+        }
+        Ok(filter)
+    }
+
+    /// The output given by the path and format options.
+    fn output(&self) -> Output {
+        let template = NamingTemplate::new(self.output.as_str());
+        let format = match self.format {
+            Some(format) => format.with_quality(self.quality),
+            None => match OutputFormat::from_path(template.as_str()) {
+                Some(OutputFormat::Jpeg(_)) => OutputFormat::Jpeg(self.quality),
+                format => format.unwrap_or_default(),
+            }
+        };
+        Output::new(template, Some(format)).with_overwrite(self.overwrite)
+    }
+}
+
+/// The failure of a capture request, whose output is written with the template.
+fn exec_failure(err: CaptureError, output: &str) -> Failure {
+    match err {
+        CaptureError::Io(_) | CaptureError::Encode(_) => (exit_code::WRITE_FAILED, format!("failed to write '{}': {}", output, err)),
+        err => capture_failure(err),
+    }
+}
+
+fn capture_failure(err: CaptureError) -> Failure {
+    match err {
+        CaptureError::PermissionDenied(_) => (exit_code::PERMISSION_DENIED, err.to_string()),
+        CaptureError::NotFound(_) => (exit_code::NOT_FOUND, err.to_string()),
+        CaptureError::InvalidRequest(_) => (exit_code::USAGE, err.to_string()),
+        _ => (exit_code::CAPTURE_FAILED, err.to_string()),
+    }
+}
+
+fn print_json(value: &impl serde::Serialize) -> Result<(), Failure> {
+    let json = serde_json::to_string_pretty(value).map_err(|err| (exit_code::WRITE_FAILED, err.to_string()))?;
+    println!("{}", json);
+    Ok(())
+}
+
+/// The longest delay or interval, in seconds.
+const MAX_SECONDS: f64 = 86_400.0;
+
+/// Parse a non-negative number of seconds up to a day, fractions allowed.
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    value.parse::<f64>().ok()
+        .filter(|seconds| *seconds <= MAX_SECONDS)
+        .and_then(|seconds| Duration::try_from_secs_f64(seconds).ok())
+        .ok_or_else(|| format!("invalid seconds '{}', expect a number from 0 to {}", value, MAX_SECONDS))
+}
+
+/// Parse a positive scale factor.
+fn parse_factor(value: &str) -> Result<f32, String> {
+    value.parse::<f32>().ok()
+        .filter(|factor| *factor > 0.0 && factor.is_finite())
+        .ok_or_else(|| format!("invalid factor '{}', expect a positive number", value))
+}
+
+/// Parse a non-negative length in points.
+fn parse_points(value: &str) -> Result<f32, String> {
+    value.parse::<f32>().ok()
+        .filter(|points| *points >= 0.0 && points.is_finite())
+        .ok_or_else(|| format!("invalid length '{}', expect a non-negative number", value))
+}
+
+/// Parse a size in WxH, both positive.
+fn parse_size(value: &str) -> Result<[u32; 2], String> {
+    value.split_once(['x', 'X'])
+        .and_then(|(w, h)| Some([w.parse().ok()?, h.parse().ok()?]))
+        .filter(|size: &[u32; 2]| size.iter().all(|&side| side > 0))
+        .ok_or_else(|| format!("invalid size '{}', expect WxH like 10x10", value))
+}
+
+/// Parse a color in RRGGBB or RRGGBBAA (with an optional leading '#').
+fn parse_color(value: &str) -> Result<[u8; 4], String> {
+    let hex = value.trim_start_matches('#');
+    if !(hex.len() == 6 || hex.len() == 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("invalid color '{}', expect RRGGBB or RRGGBBAA", value));
+    }
+
+    let mut color = [0, 0, 0, 255];
+    for (i, c) in color.iter_mut().enumerate().take(hex.len() / 2) {
+        *c = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).unwrap();
+    }
+    Ok(color)
+}
+
+#[cfg(test)]
+mod unit_test {
+    use std::fs;
+    use std::io;
+    use capture::ScreenInfo;
+    use image::RgbaImage;
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_from(["capture"].iter().chain(args))
+    }
+
+    #[test]
+    fn parse_value_test() {
+        assert_eq!(parse_color("#ff8000"), Ok([255, 128, 0, 255]));
+        assert_eq!(parse_color("0000ff80"), Ok([0, 0, 255, 128]));
+        assert!(parse_color("fff").is_err() && parse_color("gg0000").is_err());
+
+        assert_eq!(parse_seconds("1.5"), Ok(Duration::from_millis(1500)));
+        assert_eq!(parse_seconds("86400"), Ok(Duration::from_secs(86_400)));
+        assert!(parse_seconds("-1").is_err() && parse_seconds("1e19").is_err() && parse_seconds("nan").is_err());
+
+        assert_eq!(parse_factor("2"), Ok(2.0));
+        assert!(parse_factor("0").is_err() && parse_factor("-1").is_err() && parse_factor("inf").is_err());
+
+        assert_eq!(parse_points("0"), Ok(0.0));
+        assert!(parse_points("-1").is_err());
+        assert_eq!(parse_size("16x9"), Ok([16, 9]));
+        assert!(parse_size("0x9").is_err() && parse_size("16").is_err());
+    }
+
+    #[test]
+    fn parse_args_test() {
+        // the interactive crop by default
+        let cli = parse(&[]).unwrap();
+        assert!(cli.command.is_none());
+        let cli = parse(&["region", "--auto-bounding", "--edge-tolerance", "6", "--min-size", "3x4", "--no-magnifier"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Command::Region { auto_bounding: true, edge_tolerance: Some(6.0), min_size: Some([3, 4]), no_magnifier: true })
+        ));
+
+        // global options after the command, negative coordinates
+        let cli = parse(&["rect", "-10", "-20", "30", "40", "--fill", "ff0000", "--delay", "0.5"]).unwrap();
+        assert!(matches!(cli.command, Some(Command::Rect { x: -10, y: -20, width: 30, height: 40, fill: [255, 0, 0, 255] })));
+        assert_eq!(cli.delay, Some(Duration::from_millis(500)));
+
+        // bad arguments, which exit with USAGE
+        for args in [
+            &["--delay", "1e19"][..],
+            &["--cursor-scale", "2"],
+            &["--cursor", "--cursor-scale", "0"],
+            &["--shots", "3"],
+            &["--every", "1", "--shots", "0"],
+            &["--quality", "0"],
+            &["region", "--min-size", "0x1"],
+            &["nope"],
+        ] {
+            assert!(parse(args).unwrap_err().use_stderr(), "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn exit_code_test() {
+        let failure = |err: CaptureError| exec_failure(err, "out.png").0;
+        assert_eq!(failure(CaptureError::NoMonitors), exit_code::CAPTURE_FAILED);
+        assert_eq!(failure(CaptureError::Backend("gone".into())), exit_code::CAPTURE_FAILED);
+        assert_eq!(failure(CaptureError::Ui("no display".into())), exit_code::CAPTURE_FAILED);
+        assert_eq!(failure(CaptureError::InvalidRequest("too long".into())), exit_code::USAGE);
+        assert_eq!(failure(CaptureError::NotFound("no such window".into())), exit_code::NOT_FOUND);
+        assert_eq!(failure(CaptureError::Io(io::Error::other("full"))), exit_code::WRITE_FAILED);
+        assert_eq!(failure(CaptureError::PermissionDenied("screen recording".into())), exit_code::PERMISSION_DENIED);
+        assert_eq!(parse(&[]).unwrap().finish(vec![], None), Err((exit_code::CANCELLED, "cancelled".to_string())));
+
+        // end to end, on an archive instead of the live screens
+        let dir = std::env::temp_dir().join(format!("capture-cli-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let screen = ScreenInfo { name: "main".into(), is_primary: true, xywh: (0, 0, 4, 4), sf: 1.0, rgba_image: RgbaImage::new(4, 4) };
+        let archive = dir.join("desktop.capsnap");
+        Snapshot::new(vec![screen], vec![]).unwrap().save(&archive).unwrap();
+        let run = |args: &[&str]| {
+            let load = ["--load", archive.to_str().unwrap()];
+            parse(&load.iter().chain(args).copied().collect::<Vec<_>>()).unwrap().exec().map_err(|(code, _)| code)
+        };
+        let output = dir.join("shot.png");
+        assert_eq!(run(&["monitor", "0", "-o", output.to_str().unwrap()]), Ok(()));
+        assert!(output.exists());
+        assert_eq!(run(&["list-monitors", "--json"]), Ok(()));
+        assert_eq!(run(&["monitor", "9"]), Err(exit_code::NOT_FOUND));
+        assert_eq!(run(&["window", "x", "--id"]), Err(exit_code::USAGE));
+        // a file where a directory is expected
+        assert_eq!(run(&["monitor", "0", "-o", output.join("shot.png").to_str().unwrap()]), Err(exit_code::WRITE_FAILED));
+        let missing = parse(&["--load", dir.join("missing.capsnap").to_str().unwrap(), "list-monitors"]).unwrap();
+        assert_eq!(missing.exec().map_err(|(code, _)| code), Err(exit_code::CAPTURE_FAILED));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,666 @@
+use egui::epaint::text::Fonts;
+use egui::{Color32, FontDefinitions, FontId, Pos2, Rect, Vec2};
+use image::RgbaImage;
+use crate::canonical::Redaction;
+
+/// Annotation tools, in the order of the toolbar.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum Tool {
+    Rectangle,
+    Ellipse,
+    Line,
+    Arrow,
+    Pen,
+    Highlighter,
+    Text,
+    Step,
+    Pixelate,
+    Blur,
+    Blackout,
+}
+
+impl Tool {
+    pub const ALL: [Tool; 11] = [
+        Tool::Rectangle, Tool::Ellipse, Tool::Line, Tool::Arrow, Tool::Pen, Tool::Highlighter, Tool::Text, Tool::Step,
+        Tool::Pixelate, Tool::Blur, Tool::Blackout,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Tool::Rectangle => "▭",
+            Tool::Ellipse => "◯",
+            Tool::Line => "╱",
+            Tool::Arrow => "↗",
+            Tool::Pen => "✏",
+            Tool::Highlighter => "▰",
+            Tool::Text => "A",
+            Tool::Step => "①",
+            Tool::Pixelate => "▦",
+            Tool::Blur => "◌",
+            Tool::Blackout => "■",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Tool::Rectangle => "rectangle",
+            Tool::Ellipse => "ellipse",
+            Tool::Line => "line",
+            Tool::Arrow => "arrow",
+            Tool::Pen => "pen",
+            Tool::Highlighter => "highlighter",
+            Tool::Text => "text",
+            Tool::Step => "step marker",
+            Tool::Pixelate => "pixelate",
+            Tool::Blur => "blur",
+            Tool::Blackout => "blackout",
+        }
+    }
+
+    /// Whether the tool hides the pixels of an area, instead of drawing over them.
+    pub fn is_redaction(self) -> bool {
+        matches!(self, Tool::Pixelate | Tool::Blur | Tool::Blackout)
+    }
+
+    fn index(self) -> usize {
+        Tool::ALL.iter().position(|tool| *tool == self).unwrap()
+    }
+}
+
+/// Color and stroke of a tool, sizes are in physical pixels.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Style {
+    /// in RGBA format
+    pub color: [u8; 4],
+    /// width of the lines, the size of text and step markers follows it
+    pub stroke: f32,
+}
+
+impl Style {
+    /// alpha of the highlighter, which is always translucent
+    const HIGHLIGHTER_ALPHA: u8 = 96;
+
+    pub fn font_size(&self) -> f32 {
+        (self.stroke * 4.0 + 8.0).max(8.0)
+    }
+
+    pub fn marker_radius(&self) -> f32 {
+        self.stroke * 2.0 + 8.0
+    }
+
+    pub fn color32(&self) -> Color32 {
+        Color32::from_rgba_unmultiplied(self.color[0], self.color[1], self.color[2], self.color[3])
+    }
+}
+
+/// What is drawn, in physical desktop coordinates.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Shape {
+    Rectangle(Rect),
+    Ellipse(Rect),
+    Line(Pos2, Pos2),
+    Arrow(Pos2, Pos2),
+    Pen(Vec<Pos2>),
+    Highlighter(Vec<Pos2>),
+    /// top-left corner and the text
+    Text(Pos2, String),
+    /// center and the number
+    Step(Pos2, u32),
+    /// the area to hide, it is applied to the pixels of the snapshot before anything is drawn
+    Redact(Rect, Redaction),
+}
+
+/// (points, closed)
+pub type Path = (Vec<Pos2>, bool);
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Annotation {
+    pub shape: Shape,
+    pub style: Style,
+}
+
+impl Annotation {
+    /// The lines to stroke, with the width and the color to stroke them with.
+    /// Text and step markers have no lines.
+    pub fn paths(&self) -> (Vec<Path>, f32, [u8; 4]) {
+        let Style { color, stroke } = self.style;
+        match &self.shape {
+            Shape::Rectangle(rect) => {
+                (vec![(vec![rect.left_top(), rect.right_top(), rect.right_bottom(), rect.left_bottom()], true)], stroke, color)
+            }
+            Shape::Ellipse(rect) => {
+                let points = (0..64)
+                    .map(|i| {
+                        let angle = i as f32 / 64.0 * std::f32::consts::TAU;
+                        rect.center() + Vec2::new(angle.cos() * rect.width(), angle.sin() * rect.height()) / 2.0
+                    })
+                    .collect();
+                (vec![(points, true)], stroke, color)
+            }
+            Shape::Line(from, to) => (vec![(vec![*from, *to], false)], stroke, color),
+            Shape::Arrow(from, to) => {
+                // no head while it has no direction
+                if from == to {
+                    return (vec![(vec![*from, *to], false)], stroke, color);
+                }
+                let direction = (*to - *from).normalized();
+                let length = stroke * 3.0 + 8.0;
+                let head = |angle: f32| *to - rotate(direction, angle) * length;
+                let angle = std::f32::consts::FRAC_PI_6;
+                (vec![(vec![*from, *to], false), (vec![head(angle), *to, head(-angle)], false)], stroke, color)
+            }
+            Shape::Pen(points) => (vec![(points.clone(), false)], stroke, color),
+            Shape::Highlighter(points) => {
+                let [r, g, b, _] = color;
+                (vec![(points.clone(), false)], stroke * 4.0, [r, g, b, Style::HIGHLIGHTER_ALPHA])
+            }
+            Shape::Text(_, _) | Shape::Step(_, _) | Shape::Redact(_, _) => (vec![], stroke, color),
+        }
+    }
+}
+
+fn rotate(v: Vec2, angle: f32) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
+}
+
+/// Annotations being edited, with the history of undo and redo.
+pub struct Annotator {
+    /// the active tool, `None` if the pointer edits the selection instead
+    tool: Option<Tool>,
+    /// style of each tool, in the order of [`Tool::ALL`]
+    styles: [Style; Tool::ALL.len()],
+    /// settings of the pixelate and the blur tools
+    pixelate: Redaction,
+    blur: Redaction,
+    /// the finished annotations, from the bottom
+    done: Vec<Annotation>,
+    /// the undone annotations, the last one is redone first
+    undone: Vec<Annotation>,
+    /// the shape being drawn
+    drawing: Option<Annotation>,
+    /// the text being typed
+    typing: Option<Annotation>,
+}
+
+impl Annotator {
+    pub fn new(style: Style) -> Annotator {
+        Annotator {
+            tool: None,
+            styles: [style; Tool::ALL.len()],
+            pixelate: Redaction::Pixelate { block: 12 },
+            blur: Redaction::Blur { sigma: 8.0 },
+            done: vec![],
+            undone: vec![],
+            drawing: None,
+            typing: None,
+        }
+    }
+
+    /// Set the initial settings of the pixelate or the blur tool, the blackout tool has no settings.
+    pub fn with_redaction(mut self, redaction: Redaction) -> Annotator {
+        match redaction {
+            Redaction::Pixelate { .. } => self.pixelate = redaction,
+            Redaction::Blur { .. } => self.blur = redaction,
+            Redaction::Blackout => {}
+        }
+        self
+    }
+
+    pub fn tool(&self) -> Option<Tool> {
+        self.tool
+    }
+
+    /// Pick a tool, or go back to editing the selection. The text being typed is finished.
+    pub fn set_tool(&mut self, tool: Option<Tool>) {
+        self.commit_text();
+        self.tool = tool;
+    }
+
+    pub fn style_mut(&mut self, tool: Tool) -> &mut Style {
+        &mut self.styles[tool.index()]
+    }
+
+    /// Settings of a redaction tool, `None` if the tool has none.
+    pub fn redaction_mut(&mut self, tool: Tool) -> Option<&mut Redaction> {
+        match tool {
+            Tool::Pixelate => Some(&mut self.pixelate),
+            Tool::Blur => Some(&mut self.blur),
+            _ => None,
+        }
+    }
+
+    /// The finished annotations, from the bottom.
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.done
+    }
+
+    /// The annotations to show, including the unfinished ones.
+    pub fn visible(&self) -> impl Iterator<Item = &Annotation> {
+        self.done.iter().chain(self.drawing.as_ref()).chain(self.typing.as_ref())
+    }
+
+    pub fn is_typing(&self) -> bool {
+        self.typing.is_some()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty() || self.typing.is_some()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    fn push(&mut self, annotation: Annotation) {
+        self.done.push(annotation);
+        self.undone.clear();
+    }
+
+    /// The number of the next step marker.
+    fn next_step(&self) -> u32 {
+        1 + self.done.iter().filter(|annotation| matches!(annotation.shape, Shape::Step(_, _))).count() as u32
+    }
+
+    pub fn pressed(&mut self, p: Pos2) {
+        let Some(tool) = self.tool else {
+            return;
+        };
+        self.commit_text();
+
+        let style = self.styles[tool.index()];
+        let shape = match tool {
+            Tool::Rectangle => Shape::Rectangle(Rect::from_two_pos(p, p)),
+            Tool::Ellipse => Shape::Ellipse(Rect::from_two_pos(p, p)),
+            Tool::Line => Shape::Line(p, p),
+            Tool::Arrow => Shape::Arrow(p, p),
+            Tool::Pen => Shape::Pen(vec![p]),
+            Tool::Highlighter => Shape::Highlighter(vec![p]),
+            Tool::Text => {
+                self.typing = Some(Annotation { shape: Shape::Text(p, String::new()), style });
+                return;
+            }
+            Tool::Step => {
+                let shape = Shape::Step(p, self.next_step());
+                self.push(Annotation { shape, style });
+                return;
+            }
+            Tool::Pixelate => Shape::Redact(Rect::from_two_pos(p, p), self.pixelate),
+            Tool::Blur => Shape::Redact(Rect::from_two_pos(p, p), self.blur),
+            Tool::Blackout => Shape::Redact(Rect::from_two_pos(p, p), Redaction::Blackout),
+        };
+        self.drawing = Some(Annotation { shape, style });
+    }
+
+    pub fn dragged(&mut self, p: Pos2) {
+        let Some(annotation) = self.drawing.as_mut() else {
+            return;
+        };
+        match &mut annotation.shape {
+            Shape::Rectangle(rect) | Shape::Ellipse(rect) | Shape::Redact(rect, _) => {
+                // 'min' is the start point until the rect is released and normalized
+                *rect = Rect::from_min_max(rect.min, p);
+            }
+            Shape::Line(_, to) | Shape::Arrow(_, to) => *to = p,
+            Shape::Pen(points) | Shape::Highlighter(points) => {
+                if points.last().is_none_or(|last| last.distance(p) >= 1.0) {
+                    points.push(p);
+                }
+            }
+            Shape::Text(_, _) | Shape::Step(_, _) => {}
+        }
+    }
+
+    pub fn released(&mut self) {
+        let Some(mut annotation) = self.drawing.take() else {
+            return;
+        };
+        let keep = match &mut annotation.shape {
+            Shape::Rectangle(rect) | Shape::Ellipse(rect) | Shape::Redact(rect, _) => {
+                *rect = Rect::from_two_pos(rect.min, rect.max);
+                rect.area() > 0.0
+            }
+            Shape::Line(from, to) | Shape::Arrow(from, to) => from != to,
+            Shape::Pen(points) | Shape::Highlighter(points) => !points.is_empty(),
+            Shape::Text(_, _) | Shape::Step(_, _) => true,
+        };
+        if keep {
+            self.push(annotation);
+        }
+    }
+
+    pub fn type_text(&mut self, text: &str) {
+        if let Some(Annotation { shape: Shape::Text(_, typed), .. }) = self.typing.as_mut() {
+            typed.push_str(text);
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        if let Some(Annotation { shape: Shape::Text(_, typed), .. }) = self.typing.as_mut() {
+            typed.pop();
+        }
+    }
+
+    /// Finish the text being typed, empty text is dropped.
+    pub fn commit_text(&mut self) {
+        if let Some(annotation) = self.typing.take() {
+            if !matches!(&annotation.shape, Shape::Text(_, typed) if typed.is_empty()) {
+                self.push(annotation);
+            }
+        }
+    }
+
+    pub fn cancel_text(&mut self) {
+        self.typing = None;
+    }
+
+    /// Drop the text being typed, or take back the last annotation.
+    pub fn undo(&mut self) {
+        if self.typing.take().is_some() {
+            return;
+        }
+        if let Some(annotation) = self.done.pop() {
+            self.undone.push(annotation);
+        }
+    }
+
+    pub fn redo(&mut self) {
+        self.commit_text();
+        if let Some(annotation) = self.undone.pop() {
+            self.done.push(annotation);
+        }
+    }
+}
+
+/// Coverage (0.0 ~ 1.0) of the pixels of an image, which is painted with a single color at once,
+/// so that overlapping parts of a translucent shape are not painted twice.
+struct Mask {
+    width: u32,
+    height: u32,
+    coverage: Vec<f32>,
+}
+
+impl Mask {
+    fn new(width: u32, height: u32) -> Mask {
+        Mask { width, height, coverage: vec![0.0; (width * height) as usize] }
+    }
+
+    /// Pixels (with their centers) around the rect, clipped to the image.
+    fn pixels_around(&self, rect: Rect) -> impl Iterator<Item = (u32, u32, Pos2)> {
+        let x0 = rect.left().floor().max(0.0) as u32;
+        let y0 = rect.top().floor().max(0.0) as u32;
+        let x1 = (rect.right().ceil().max(0.0) as u32).min(self.width);
+        let y1 = (rect.bottom().ceil().max(0.0) as u32).min(self.height);
+        (y0..y1).flat_map(move |y| (x0..x1).map(move |x| (x, y, Pos2::new(x as f32 + 0.5, y as f32 + 0.5))))
+    }
+
+    fn cover(&mut self, x: u32, y: u32, coverage: f32) {
+        let c = &mut self.coverage[(y * self.width + x) as usize];
+        *c = c.max(coverage.clamp(0.0, 1.0));
+    }
+
+    /// A line of the width with round ends.
+    fn segment(&mut self, a: Pos2, b: Pos2, width: f32) {
+        let r = width / 2.0;
+        let area = Rect::from_two_pos(a, b).expand(r + 1.0);
+        let pixels: Vec<_> = self.pixels_around(area).collect();
+        for (x, y, p) in pixels {
+            let ab = b - a;
+            let t = if ab.length_sq() == 0.0 { 0.0 } else { ((p - a).dot(ab) / ab.length_sq()).clamp(0.0, 1.0) };
+            let distance = p.distance(a + ab * t);
+            self.cover(x, y, r + 0.5 - distance);
+        }
+    }
+
+    fn path(&mut self, points: &[Pos2], closed: bool, width: f32) {
+        match points {
+            [] => {}
+            [p] => self.segment(*p, *p, width),
+            _ => {
+                for pair in points.windows(2) {
+                    self.segment(pair[0], pair[1], width);
+                }
+                if closed {
+                    self.segment(points[points.len() - 1], points[0], width);
+                }
+            }
+        }
+    }
+
+    fn circle(&mut self, center: Pos2, radius: f32) {
+        let pixels: Vec<_> = self.pixels_around(Rect::from_center_size(center, Vec2::splat(radius * 2.0 + 2.0))).collect();
+        for (x, y, p) in pixels {
+            self.cover(x, y, radius + 0.5 - p.distance(center));
+        }
+    }
+
+    /// The glyphs of the text, whose top-left corner is at the point.
+    fn text(&mut self, fonts: &Fonts, pos: Pos2, text: &str, size: f32) {
+        let galley = fonts.layout_no_wrap(text.to_string(), FontId::proportional(size), Color32::WHITE);
+        let atlas = fonts.image();
+        for row in &galley.rows {
+            for glyph in &row.glyphs {
+                let uv = glyph.uv_rect;
+                if uv.is_nothing() {
+                    continue;
+                }
+                let left_top = (pos + glyph.pos.to_vec2() + uv.offset).round();
+                for ty in uv.min[1]..uv.max[1] {
+                    for tx in uv.min[0]..uv.max[0] {
+                        let x = left_top.x as i64 + (tx - uv.min[0]) as i64;
+                        let y = left_top.y as i64 + (ty - uv.min[1]) as i64;
+                        if x >= 0 && y >= 0 && (x as u32) < self.width && (y as u32) < self.height {
+                            self.cover(x as u32, y as u32, atlas.pixels[ty as usize * atlas.size[0] + tx as usize]);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Paint the color (in RGBA format, not premultiplied) over the image by the coverage, and reset the mask.
+    fn paint(&mut self, image: &mut RgbaImage, color: [u8; 4]) {
+        for (i, c) in self.coverage.iter_mut().enumerate() {
+            if *c <= 0.0 {
+                continue;
+            }
+            let alpha = color[3] as f32 / 255.0 * *c;
+            let pixel = image.get_pixel_mut(i as u32 % self.width, i as u32 / self.width);
+            for channel in 0..3 {
+                pixel[channel] = (color[channel] as f32 * alpha + pixel[channel] as f32 * (1.0 - alpha)).round() as u8;
+            }
+            pixel[3] = (alpha * 255.0 + pixel[3] as f32 * (1.0 - alpha)).round() as u8;
+            *c = 0.0;
+        }
+    }
+}
+
+/// Paint the annotations onto the image, which shows the desktop from `origin` with `scale` pixels per unit.
+///
+/// The redactions are applied first, so that they only hide the pixels of the image, not the other annotations.
+pub fn flatten(image: &mut RgbaImage, annotations: &[Annotation], origin: Pos2, scale: f32) {
+    for annotation in annotations {
+        if let Shape::Redact(rect, redaction) = &annotation.shape {
+            // whole pixels covering the area
+            let min = ((rect.min - origin) * scale).floor();
+            let max = ((rect.max - origin) * scale).ceil();
+            redaction.apply(image, (min.x as i32, min.y as i32, (max.x - min.x) as u32, (max.y - min.y) as u32));
+        }
+    }
+
+    let (width, height) = image.dimensions();
+    let mut mask = Mask::new(width, height);
+    let to_image = |p: Pos2| Pos2::ZERO + (p - origin) * scale;
+
+    // the fonts are only loaded when there is text
+    let mut fonts: Option<Fonts> = None;
+    let mut fonts = || fonts.get_or_insert_with(|| Fonts::new(1.0, 8192, FontDefinitions::default())).clone();
+
+    for annotation in annotations {
+        match &annotation.shape {
+            Shape::Text(pos, text) => {
+                mask.text(&fonts(), to_image(*pos), text, annotation.style.font_size() * scale);
+                mask.paint(image, annotation.style.color);
+            }
+            Shape::Step(center, number) => {
+                let radius = annotation.style.marker_radius() * scale;
+                let center = to_image(*center);
+                mask.circle(center, radius);
+                mask.paint(image, annotation.style.color);
+
+                let fonts = fonts();
+                let text = number.to_string();
+                let size = fonts.layout_no_wrap(text.clone(), FontId::proportional(radius * 1.2), Color32::WHITE).size();
+                mask.text(&fonts, center - size / 2.0, &text, radius * 1.2);
+                mask.paint(image, [255, 255, 255, 255]);
+            }
+            Shape::Redact(_, _) => {}
+            _ => {
+                let (paths, width, color) = annotation.paths();
+                for (points, closed) in paths {
+                    let points: Vec<Pos2> = points.into_iter().map(to_image).collect();
+                    mask.path(&points, closed, width * scale);
+                }
+                mask.paint(image, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use image::Rgba;
+    use super::*;
+
+    const RED: Style = Style { color: [255, 0, 0, 255], stroke: 2.0 };
+
+    #[test]
+    fn history_test() {
+        let mut annotator = Annotator::new(RED);
+
+        // nothing happens without a tool
+        annotator.pressed(Pos2::new(10.0, 10.0));
+        annotator.released();
+        assert!(annotator.annotations().is_empty());
+
+        // a rect drawn from the bottom-right corner
+        annotator.set_tool(Some(Tool::Rectangle));
+        annotator.pressed(Pos2::new(50.0, 50.0));
+        annotator.dragged(Pos2::new(10.0, 20.0));
+        assert_eq!(annotator.visible().count(), 1);
+        annotator.released();
+        assert_eq!(annotator.annotations()[0].shape, Shape::Rectangle(Rect::from_min_max(Pos2::new(10.0, 20.0), Pos2::new(50.0, 50.0))));
+
+        // a click is not a line
+        annotator.set_tool(Some(Tool::Line));
+        annotator.pressed(Pos2::new(10.0, 10.0));
+        annotator.released();
+        assert_eq!(annotator.annotations().len(), 1);
+
+        // step markers are numbered
+        annotator.set_tool(Some(Tool::Step));
+        annotator.pressed(Pos2::new(10.0, 10.0));
+        annotator.pressed(Pos2::new(20.0, 10.0));
+        assert_eq!(annotator.annotations()[2].shape, Shape::Step(Pos2::new(20.0, 10.0), 2));
+
+        // text is committed by the next click
+        annotator.set_tool(Some(Tool::Text));
+        *annotator.style_mut(Tool::Text) = Style { color: [0, 0, 255, 255], stroke: 4.0 };
+        annotator.pressed(Pos2::new(30.0, 30.0));
+        annotator.type_text("hi!");
+        annotator.backspace();
+        assert!(annotator.is_typing());
+        annotator.pressed(Pos2::new(40.0, 40.0));
+        assert_eq!(annotator.annotations()[3].shape, Shape::Text(Pos2::new(30.0, 30.0), "hi".into()));
+        assert_eq!(annotator.annotations()[3].style.color, [0, 0, 255, 255]);
+        assert_eq!(annotator.annotations()[0].style, RED);
+
+        // undo drops the empty text first, then the annotations
+        annotator.undo();
+        assert!(!annotator.is_typing());
+        assert_eq!(annotator.annotations().len(), 4);
+        annotator.undo();
+        annotator.undo();
+        assert_eq!(annotator.annotations().len(), 2);
+        annotator.redo();
+        assert_eq!(annotator.annotations().len(), 3);
+
+        // a new annotation drops the redo history
+        annotator.pressed(Pos2::new(50.0, 50.0));
+        annotator.type_text("x");
+        annotator.commit_text();
+        assert!(!annotator.can_redo());
+        assert_eq!(annotator.annotations().len(), 4);
+
+        // redactions are areas, with the settings of the tool
+        let mut annotator = Annotator::new(RED).with_redaction(Redaction::Pixelate { block: 4 });
+        annotator.set_tool(Some(Tool::Pixelate));
+        annotator.pressed(Pos2::new(30.0, 10.0));
+        annotator.dragged(Pos2::new(10.0, 30.0));
+        annotator.released();
+        let rect = Rect::from_min_max(Pos2::new(10.0, 10.0), Pos2::new(30.0, 30.0));
+        assert_eq!(annotator.annotations()[0].shape, Shape::Redact(rect, Redaction::Pixelate { block: 4 }));
+        assert!(annotator.redaction_mut(Tool::Blackout).is_none());
+    }
+
+    #[test]
+    fn arrow_test() {
+        let arrow = |to: Pos2| Annotation { shape: Shape::Arrow(Pos2::new(10.0, 10.0), to), style: RED }.paths().0;
+
+        // the head is at the tip, on both sides of the shaft
+        let paths = arrow(Pos2::new(50.0, 10.0));
+        assert_eq!(paths.len(), 2);
+        let head = &paths[1].0;
+        assert_eq!(head[1], Pos2::new(50.0, 10.0));
+        assert!(head[0].x < 50.0 && head[0].y < 10.0 && head[2].x < 50.0 && head[2].y > 10.0);
+
+        // no head on a zero length, as when the drag starts
+        let paths = arrow(Pos2::new(10.0, 10.0));
+        assert_eq!(paths.len(), 1);
+        assert!(paths.iter().flat_map(|(points, _)| points).all(|p| p.x.is_finite() && p.y.is_finite()));
+    }
+
+    #[test]
+    fn flatten_test() {
+        let white = Rgba([255, 255, 255, 255]);
+        let annotation = |shape: Shape| Annotation { shape, style: RED };
+
+        // the desktop from (100, 100), at 2 pixels per unit
+        let origin = Pos2::new(100.0, 100.0);
+        let mut image = RgbaImage::from_pixel(100, 100, white);
+        flatten(&mut image, &[
+            annotation(Shape::Line(Pos2::new(100.0, 110.0), Pos2::new(150.0, 110.0))),
+            annotation(Shape::Highlighter(vec![Pos2::new(110.0, 130.0), Pos2::new(110.0, 140.0), Pos2::new(110.0, 130.0)])),
+        ], origin, 2.0);
+
+        // the line is 4 pixels wide at y = 20
+        assert_eq!(image.get_pixel(50, 19).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(50, 21).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(50, 24).0, [255, 255, 255, 255]);
+        // the highlighter is translucent, even where it overlaps itself
+        let highlighted = image.get_pixel(20, 70).0;
+        assert_eq!(highlighted, [255, 159, 159, 255]);
+        assert_eq!(image.get_pixel(20, 62).0, highlighted);
+
+        // text and markers are painted with the fonts of the UI
+        let mut image = RgbaImage::from_pixel(100, 100, white);
+        flatten(&mut image, &[
+            annotation(Shape::Text(Pos2::new(0.0, 0.0), "W".into())),
+            annotation(Shape::Step(Pos2::new(70.0, 70.0), 1)),
+        ], Pos2::ZERO, 1.0);
+        assert!((0..16).any(|y| (0..16).any(|x| image.get_pixel(x, y).0 == [255, 0, 0, 255])));
+        assert_eq!(image.get_pixel(70 - 11, 70).0, [255, 0, 0, 255]);
+        assert_eq!(image.get_pixel(50, 50).0, [255, 255, 255, 255]);
+
+        // redactions cover whole pixels, and are applied before the other annotations whatever the order
+        let mut image = RgbaImage::from_pixel(20, 20, white);
+        flatten(&mut image, &[
+            annotation(Shape::Line(Pos2::new(0.0, 10.0), Pos2::new(20.0, 10.0))),
+            annotation(Shape::Redact(Rect::from_min_max(Pos2::new(4.2, 4.2), Pos2::new(8.5, 15.0)), Redaction::Blackout)),
+        ], Pos2::ZERO, 1.0);
+        assert_eq!(image.get_pixel(4, 4).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(8, 14).0, [0, 0, 0, 255]);
+        assert_eq!(image.get_pixel(9, 4).0, [255, 255, 255, 255]);
+        assert_eq!(image.get_pixel(6, 10).0, [255, 0, 0, 255]);
+    }
+}
@@ -1,30 +1,11 @@
 use std::cell::RefCell;
 use std::rc::Rc;
-use egui::{Frame, Color32, Context, Key, ViewportCommand, Image, Rect, Pos2, Vec2, Ui, Rounding, CursorIcon, Event};
+use egui::{Frame, Color32, Context, Key, ViewportCommand, Image, Rect, Pos2, Ui, Rounding, CursorIcon, Stroke, Vec2, FontId, Align2, Area, Event, Id, Slider, ColorImage, TextureHandle, TextureOptions};
 use image::RgbaImage;
-use crate::canonical::{Snapshot};
+use crate::canonical::{DesktopSpace, LogicalPos, LogicalRect, PhysicalPos, PhysicalRect, Redaction, Snapshot};
+use crate::cropper::annotate::{flatten, Annotation, Annotator, Shape, Style, Tool};
 use crate::cropper::config::CropperConfig;
-
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
-enum PositionRelation {
-    Inside,
-    Outside,
-    /// we use something like one-hot encoding to represent the position relation.
-    ///
-    /// THAT IS:
-    ///
-    /// | Position     | Code |
-    /// |--------------|------|
-    /// | top-left     | 5    |
-    /// | top-right    | 3    |
-    /// | bottom-right | 8    |
-    /// | bottom-left  | 10   |
-    /// | top          | 1    |
-    /// | right        | 2    |
-    /// | bottom       | 6    |
-    /// | left         | 4    |
-    Edge(u8),
-}
+use crate::cropper::session::{handle_rects, AppState, CropSession, PositionRelation, SessionEvent, SessionKey, SessionOutcome};
 
 impl From<PositionRelation> for CursorIcon {
     fn from(value: PositionRelation) -> Self {
@@ -40,158 +21,524 @@ impl From<PositionRelation> for CursorIcon {
                 2 => CursorIcon::ResizeEast,
                 6 => CursorIcon::ResizeSouth,
                 4 => CursorIcon::ResizeWest,
-                _ => unreachable!("this code should not be reached")
+                _ => CursorIcon::Default,
             }
         }
     }
 }
 
-fn get_position_relation(bounding: Rect, point: Pos2) -> PositionRelation {
-    let Pos2 { x: px, y: py } = point;
-    let Rect { min: Pos2 { x: bxl, y: byt }, max: Pos2 { x: bxr, y: byb } } = bounding;
+/// color of the outline of the window under the pointer
+const HIGHLIGHT_COLOR: Color32 = Color32::from_rgb(0, 120, 215);
 
-    let mut code = 0u8;
-    if px == bxl {
-        code += 4;
-    } else if px == bxr {
-        code += 2;
+// the session works with egui types, but in physical coordinates
+impl From<PhysicalPos> for Pos2 {
+    fn from(value: PhysicalPos) -> Self {
+        Pos2::new(value.x, value.y)
     }
-    if py == byt {
-        code += 1;
-    } else if py == byb {
-        code += 6;
+}
+
+impl From<Pos2> for PhysicalPos {
+    fn from(value: Pos2) -> Self {
+        PhysicalPos { x: value.x, y: value.y }
     }
+}
 
-    if code == 0 {
-        if px > bxl && px < bxr && py > byt && py < byb {
-            PositionRelation::Inside
-        } else {
-            PositionRelation::Outside
-        }
-    } else {
-        PositionRelation::Edge(code)
+impl From<PhysicalRect> for Rect {
+    fn from(value: PhysicalRect) -> Self {
+        Rect::from_two_pos(value.min.into(), value.max.into())
     }
 }
 
-fn apply_move(rect: Rect, modify: Vec2) -> Rect {
-    // TODO: bound in screen area
-    rect.translate(modify)
+impl From<Rect> for PhysicalRect {
+    fn from(value: Rect) -> Self {
+        PhysicalRect { min: value.min.into(), max: value.max.into() }
+    }
 }
 
-fn apply_resize(rect: Rect, modify: Vec2, code: u8) -> Rect {
-    let Rect { min, max } = rect;
+// the UI works with egui types in logical coordinates
+impl From<Pos2> for LogicalPos {
+    fn from(value: Pos2) -> Self {
+        LogicalPos { x: value.x, y: value.y }
+    }
+}
 
-    match code {
-        5 => Rect::from_two_pos(min + modify, max),
-        3 => Rect::from_two_pos(Pos2::new(min.x, min.y + modify.y), Pos2::new(max.x + modify.x, max.y)),
-        8 => Rect::from_two_pos(min, max + modify),
-        10 => Rect::from_two_pos(Pos2::new(min.x + modify.x, min.y), Pos2::new(max.x, max.y + modify.y)),
-        1 => Rect::from_two_pos(Pos2::new(min.x, min.y + modify.y), max),
-        2 => Rect::from_two_pos(min, Pos2::new(max.x + modify.x, max.y)),
-        6 => Rect::from_two_pos(min, Pos2::new(max.x, max.y + modify.y)),
-        4 => Rect::from_two_pos(Pos2::new(min.x + modify.x, min.y), max),
-        _ => unreachable!("this code should not be reached")
+impl From<LogicalRect> for Rect {
+    fn from(value: LogicalRect) -> Self {
+        Rect::from_two_pos(Pos2::new(value.min.x, value.min.y), Pos2::new(value.max.x, value.max.y))
     }
 }
 
-#[derive(Eq, PartialEq, Debug)]
-enum AppState {
-    // primary button is up, no crop area
-    Idle,
-    /// primary button is down, crop area is updating.
-    /// - (start point)
-    Cropping(Pos2),
-    /// primary button is up, crop area is fixed
-    Cropped,
-    /// primary button is down/released, but we ignore the event and do nothing
-    /// this case happens when the primary button is pressed outside the crop area
-    Ignored,
-    /// primary button is down, crop area is moving
-    /// - (crop area, start point)
-    Moving(Rect, Pos2),
-    /// primary button is down, crop area is resizing
-    /// - (crop area, start point, code)
-    Resizing(Rect, Pos2, u8),
+/// Where to put the size label of the selection: above its top-left corner, or below it when there is no room,
+/// or inside it when there is no room either. It is kept inside the canvas horizontally.
+fn label_position(selection: Rect, label: Vec2, canvas: Rect, gap: f32) -> Pos2 {
+    let y = if selection.top() - gap - label.y >= canvas.top() {
+        selection.top() - gap - label.y
+    } else if selection.bottom() + gap + label.y <= canvas.bottom() {
+        selection.bottom() + gap
+    } else {
+        selection.top() + gap
+    };
+    let x = selection.left().min(canvas.right() - label.x).max(canvas.left());
+    Pos2::new(x, y)
 }
 
-struct Helper {
-    /// bottom-right position of the application window
-    max_point: Pos2,
-    /// (name, position, size, data)
-    fragments: Vec<(String, Pos2, Vec2, Vec<u8>)>,
-    mask_color: Color32,
+/// Where to put the toolbar: below the bottom-left corner of the selection, or above it when there is no room,
+/// or inside it when there is no room either. It is kept inside the canvas horizontally.
+fn toolbar_position(selection: Rect, toolbar: Vec2, canvas: Rect, gap: f32) -> Pos2 {
+    let y = if selection.bottom() + gap + toolbar.y <= canvas.bottom() {
+        selection.bottom() + gap
+    } else if selection.top() - gap - toolbar.y >= canvas.top() {
+        selection.top() - gap - toolbar.y
+    } else {
+        selection.bottom() - gap - toolbar.y
+    };
+    let x = selection.left().min(canvas.right() - toolbar.x).max(canvas.left());
+    Pos2::new(x, y)
+}
 
-    /// state of the application
-    app_state: AppState,
+/// Where to put the magnifier: below and to the right of the pointer, or on the other side of the pointer
+/// on each axis where there is no room.
+fn loupe_position(pointer: Pos2, loupe: Vec2, canvas: Rect, gap: f32) -> Pos2 {
+    let x = if pointer.x + gap + loupe.x <= canvas.right() {
+        pointer.x + gap
+    } else {
+        pointer.x - gap - loupe.x
+    };
+    let y = if pointer.y + gap + loupe.y <= canvas.bottom() {
+        pointer.y + gap
+    } else {
+        pointer.y - gap - loupe.y
+    };
+    Pos2::new(x, y)
+}
 
-    /// rect of the crop area
-    crop_area: Option<Rect>,
+/// The color as `#RRGGBB`, the alpha is left out.
+fn hex_color(rgba: [u8; 4]) -> String {
+    format!("#{:02X}{:02X}{:02X}", rgba[0], rgba[1], rgba[2])
+}
+
+struct Helper {
+    /// the captured pixels, which the result is cut from
+    snapshot: Snapshot,
+    /// (name, bounding box, data)
+    fragments: Vec<(String, PhysicalRect, Vec<u8>)>,
+    mask_color: Color32,
+    /// side length of the resize handles, in points
+    handle_size: f32,
+    handle_color: Color32,
+    /// in points
+    edge_tolerance: f32,
+    /// (pixels across, points per pixel), `None` if the magnifier is disabled
+    magnifier: Option<(u32, f32)>,
+
+    /// mapping between the UI and the desktop, by the scale the window is rendered with
+    desktop: DesktopSpace,
+
+    /// state of the interactive cropping
+    session: CropSession,
+
+    /// annotations on the selection, in physical coordinates
+    annotator: Annotator,
+    /// where the toolbar was drawn in the last frame, in points
+    toolbar: Option<Rect>,
+    /// the finished redactions the previews are made of, from the bottom
+    redactions: Vec<Annotation>,
+    /// the redacted pixels of each finished redaction, in the same order
+    previews: Vec<TextureHandle>,
 }
 
 impl Helper {
     pub fn new(snapshot: Snapshot, config: CropperConfig) -> Helper {
-        // offset to apply from screen coordinates to in-app coordinates
-        let (offset_x, offset_y, app_w, app_h) = snapshot.xywh;
+        let bounds = PhysicalRect::from_xywh(snapshot.xywh);
+        let desktop = DesktopSpace::native(&snapshot.screens);
 
         // fragments to draw
         let mut fragments = vec![];
         for screen in &snapshot.screens {
-            let (x, y, w, h) = screen.xywh;
             fragments.push((
                 screen.name.clone(),
-                Pos2::new((x - offset_x) as f32, (y - offset_y) as f32),
-                Vec2::new(w as f32, h as f32),
+                PhysicalRect::from_xywh(screen.xywh),
                 screen.buffer(),
             ));
         }
 
-        Helper {
-            max_point: Pos2::new(app_w as f32, app_h as f32),
+        // windows to pick, from the topmost, the invisible ones are skipped
+        let windows = snapshot.apps.iter()
+            .filter(|app| config.auto_bounding && !app.is_minimized && !app.is_occluded && !app.is_off_screen)
+            .map(|app| PhysicalRect::from_xywh(app.xywh).into())
+            .collect();
+
+        let mut helper = Helper {
+            session: CropSession::new(bounds.into())
+                .with_windows(windows)
+                .with_monitors(snapshot.screens.iter().map(|screen| PhysicalRect::from_xywh(screen.xywh).into()).collect())
+                .with_min_size(Vec2::new(config.min_size[0] as f32, config.min_size[1] as f32)),
+            snapshot,
             fragments,
             mask_color: config.get_mask_color(),
-            app_state: AppState::Idle,
-            crop_area: None,
+            handle_size: config.handle_size,
+            handle_color: config.get_handle_color(),
+            edge_tolerance: config.edge_tolerance,
+            magnifier: config.magnifier.then_some((config.magnifier_pixels.max(1) | 1, config.magnifier_zoom)),
+            desktop,
+            annotator: Annotator::new(Style { color: config.annotation_color, stroke: config.annotation_stroke })
+                .with_redaction(Redaction::Pixelate { block: config.pixelate_block })
+                .with_redaction(Redaction::Blur { sigma: config.blur_sigma }),
+            toolbar: None,
+            redactions: vec![],
+            previews: vec![],
+        };
+        let ppp = helper.ppp();
+        helper.session.set_hit_area(helper.handle_size * ppp, helper.edge_tolerance * ppp);
+        helper
+    }
+
+    /// Follow the scale egui renders the window with, e.g. after it is moved to a screen of another scale factor,
+    /// and place the window over the whole desktop again.
+    pub fn update_scale(&mut self, ctx: &Context) {
+        if self.set_pixels_per_point(ctx.pixels_per_point()) {
+            let LogicalRect { min, max } = self.desktop.viewport();
+            ctx.send_viewport_cmd(ViewportCommand::OuterPosition(Pos2::new(min.x, min.y)));
+            ctx.send_viewport_cmd(ViewportCommand::InnerSize(Vec2::new(max.x - min.x, max.y - min.y)));
+        }
+    }
+
+    /// Map the UI with the scale, the hit areas are given in points. Whether the scale changes.
+    fn set_pixels_per_point(&mut self, pixels_per_point: f32) -> bool {
+        if self.desktop.pixels_per_point() == pixels_per_point {
+            return false;
+        }
+        self.desktop = DesktopSpace::new(&self.snapshot.screens, pixels_per_point);
+        let ppp = self.ppp();
+        self.session.set_hit_area(self.handle_size * ppp, self.edge_tolerance * ppp);
+        true
+    }
+
+    /// Physical rect to UI rect.
+    fn to_ui(&self, rect: PhysicalRect) -> Rect {
+        self.desktop.to_logical_rect(rect).into()
+    }
+
+    /// UI point to physical point.
+    fn to_physical(&self, pos: Pos2) -> Pos2 {
+        self.desktop.to_physical(pos.into()).into()
+    }
+
+    /// Physical point to UI point.
+    fn to_ui_pos(&self, pos: Pos2) -> Pos2 {
+        let pos = self.desktop.to_logical(pos.into());
+        Pos2::new(pos.x, pos.y)
+    }
+
+    /// Physical units per point.
+    fn ppp(&self) -> f32 {
+        self.desktop.space().ppp
+    }
+
+    /// The whole desktop in the UI.
+    fn canvas(&self) -> Rect {
+        self.desktop.canvas().into()
+    }
+
+    /// Cut the confirmed crop area out of the snapshot, at the native resolution, with the annotations on it.
+    pub fn crop(&mut self) -> Option<RgbaImage> {
+        let SessionOutcome::Confirmed(rect) = self.session.outcome() else {
+            return None;
+        };
+        self.annotator.commit_text();
+
+        let (x, y, w, h) = PhysicalRect::from(rect).to_xywh();
+        let mut image = self.snapshot.crop_native((x, y, w, h));
+        if w > 0 {
+            let scale = image.width() as f32 / w as f32;
+            flatten(&mut image, self.annotator.annotations(), Pos2::new(x as f32, y as f32), scale);
         }
+        Some(image)
     }
 
     pub fn draw_screens(&self, ui: &mut Ui) {
         let fragments = self.fragments.clone();
         for fragment in fragments {
-            let (name, pos, size, data) = fragment;
-            ui.put(Rect::from_min_size(pos, size), Image::from_bytes(name, data));
+            let (name, rect, data) = fragment;
+            ui.put(self.to_ui(rect), Image::from_bytes(name, data));
         }
     }
 
     pub fn draw_crop(&self, ui: &mut Ui) {
-        if let Some(rect) = self.crop_area {
-            let tl_o = Pos2::ZERO;
-            let tr_o = Pos2::new(self.max_point.x, 0.0);
-            let br_o = self.max_point;
-            let bl_o = Pos2::new(0.0, self.max_point.y);
-
-            let parts = [
-                Rect::from_two_pos(tl_o, rect.right_top()),
-                Rect::from_two_pos(tr_o, rect.right_bottom()),
-                Rect::from_two_pos(br_o, rect.left_bottom()),
-                Rect::from_two_pos(bl_o, rect.left_top()),
-            ];
-            for part in parts.into_iter() {
-                ui.painter().rect_filled(part, Rounding::ZERO, self.mask_color);
+        if let Some(rect) = self.session.hovered() {
+            let rect = self.to_ui(rect.into());
+            self.draw_mask(ui, rect);
+            ui.painter().rect_stroke(rect, Rounding::ZERO, Stroke::new(2.0, HIGHLIGHT_COLOR));
+        }
+
+        if let Some(rect) = self.session.selection() {
+            // the size of the result, in the pixels of the screens
+            let (w, h) = self.snapshot.native_size(PhysicalRect::from(rect).to_xywh());
+            let rect = self.to_ui(rect.into());
+            self.draw_mask(ui, rect);
+
+            // resize handles
+            let painter = ui.painter();
+            for (_, area) in handle_rects(rect, self.handle_size) {
+                painter.rect_filled(area, Rounding::ZERO, self.handle_color);
+                painter.rect_stroke(area, Rounding::ZERO, Stroke::new(1.0, Color32::from_black_alpha(160)));
             }
 
-            // TODO: resize handles *8
+            // size indicator
+            let galley = painter.layout_no_wrap(format!("{} × {}", w, h), FontId::monospace(12.0), Color32::WHITE);
+            let padding = Vec2::new(6.0, 3.0);
+            let size = galley.size() + padding * 2.0;
+            let pos = label_position(rect, size, self.canvas(), self.handle_size);
+            painter.rect_filled(Rect::from_min_size(pos, size), Rounding::same(3.0), Color32::from_black_alpha(192));
+            painter.galley(pos + padding, galley, Color32::WHITE);
+        }
+    }
+
+    /// Make the textures of the finished redactions, and drop the ones which are undone.
+    ///
+    /// The redactions are applied one after another, as in the result, so each preview depends on the ones below it
+    /// and is made again when any of them changes.
+    pub fn update_previews(&mut self, ctx: &Context) {
+        let redactions: Vec<Annotation> = self.annotator.annotations().iter()
+            .filter(|annotation| matches!(annotation.shape, Shape::Redact(_, _)))
+            .cloned()
+            .collect();
+        let kept = self.redactions.iter().zip(&redactions).take_while(|(a, b)| a == b).count();
+        self.previews.truncate(kept);
+
+        for index in kept..redactions.len() {
+            let Shape::Redact(rect, _) = redactions[index].shape else {
+                continue;
+            };
+            // the redactions below it may reach into it from outside
+            let area = redactions[..index].iter()
+                .filter_map(|annotation| match annotation.shape {
+                    Shape::Redact(below, _) => Some(below),
+                    _ => None,
+                })
+                .fold(rect, Rect::union);
+            let (x, y, w, h) = PhysicalRect::from(area).to_xywh();
+            let mut image = self.snapshot.crop_native((x, y, w, h));
+            let scale = if w > 0 { image.width() as f32 / w as f32 } else { 1.0 };
+            let origin = Pos2::new(x as f32, y as f32);
+            flatten(&mut image, &redactions[..=index], origin, scale);
+
+            // the same pixels as 'flatten' covers
+            let min = ((rect.min - origin) * scale).floor();
+            let max = ((rect.max - origin) * scale).ceil();
+            let image = image::imageops::crop_imm(&image, min.x as u32, min.y as u32, (max.x - min.x) as u32, (max.y - min.y) as u32).to_image();
+            let (w, h) = image.dimensions();
+            let image = ColorImage::from_rgba_unmultiplied([w as usize, h as usize], image.as_raw());
+            self.previews.push(ctx.load_texture(format!("redaction-{}", index), image, TextureOptions::NEAREST));
+        }
+        self.redactions = redactions;
+    }
+
+    /// Draw the annotations, the finished ones and the one being drawn or typed.
+    pub fn draw_annotations(&self, ui: &mut Ui) {
+        let painter = ui.painter();
+
+        // redactions are below everything else, as in the result
+        let redactions = self.annotator.visible().filter_map(|annotation| match &annotation.shape {
+            Shape::Redact(rect, _) => Some(rect),
+            _ => None,
+        });
+        for (index, rect) in redactions.enumerate() {
+            let area = self.to_ui((*rect).into());
+            match self.previews.get(index) {
+                Some(texture) => {
+                    let uv = Rect::from_min_max(Pos2::ZERO, Pos2::new(1.0, 1.0));
+                    painter.image(texture.id(), area, uv, Color32::WHITE);
+                }
+                // being drawn
+                None => {
+                    painter.rect_stroke(area, Rounding::ZERO, Stroke::new(1.0, self.handle_color));
+                }
+            }
+        }
+
+        let count = self.annotator.visible().count();
+        for (index, annotation) in self.annotator.visible().enumerate() {
+            match &annotation.shape {
+                Shape::Redact(_, _) => {}
+                Shape::Text(pos, text) => {
+                    // the text being typed is always the last one, with a caret
+                    let caret = self.annotator.is_typing() && index + 1 == count;
+                    let text = if caret { format!("{}|", text) } else { text.clone() };
+                    let font = FontId::proportional(annotation.style.font_size() / self.ppp());
+                    painter.text(self.to_ui_pos(*pos), Align2::LEFT_TOP, text, font, annotation.style.color32());
+                }
+                Shape::Step(center, number) => {
+                    let radius = annotation.style.marker_radius() / self.ppp();
+                    let center = self.to_ui_pos(*center);
+                    painter.circle_filled(center, radius, annotation.style.color32());
+                    let font = FontId::proportional(radius * 1.2);
+                    painter.text(center, Align2::CENTER_CENTER, number.to_string(), font, Color32::WHITE);
+                }
+                _ => {
+                    let (paths, width, color) = annotation.paths();
+                    let stroke = Stroke::new(width / self.ppp(), Color32::from_rgba_unmultiplied(color[0], color[1], color[2], color[3]));
+                    for (points, closed) in paths {
+                        let points = points.into_iter().map(|p| self.to_ui_pos(p)).collect();
+                        painter.add(match closed {
+                            true => egui::Shape::closed_line(points, stroke),
+                            false => egui::Shape::line(points, stroke),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    /// Draw the annotation tools next to the selection, once it is cropped.
+    pub fn draw_toolbar(&mut self, ctx: &Context) {
+        let Some(selection) = self.session.selection().filter(|_| self.session.state() == AppState::Cropped) else {
+            self.toolbar = None;
+            return;
+        };
+        let size = self.toolbar.map_or(Vec2::new(480.0, 32.0), |rect| rect.size());
+        let pos = toolbar_position(self.to_ui(selection.into()), size, self.canvas(), self.handle_size);
+
+        let annotator = &mut self.annotator;
+        let response = Area::new(Id::new("toolbar")).fixed_pos(pos).show(ctx, |ui| {
+            Frame::popup(ui.style()).show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    for tool in Tool::ALL {
+                        let active = annotator.tool() == Some(tool);
+                        if ui.selectable_label(active, tool.label()).on_hover_text(tool.name()).clicked() {
+                            annotator.set_tool(if active { None } else { Some(tool) });
+                        }
+                    }
+
+                    match annotator.tool() {
+                        Some(tool) if tool.is_redaction() => match annotator.redaction_mut(tool) {
+                            Some(Redaction::Pixelate { block }) => {
+                                ui.separator();
+                                ui.add(Slider::new(block, Redaction::MIN_BLOCK..=64).show_value(false)).on_hover_text("block size");
+                            }
+                            Some(Redaction::Blur { sigma }) => {
+                                ui.separator();
+                                ui.add(Slider::new(sigma, Redaction::MIN_SIGMA..=32.0).show_value(false)).on_hover_text("strength");
+                            }
+                            _ => {}
+                        }
+                        Some(tool) => {
+                            ui.separator();
+                            let style = annotator.style_mut(tool);
+                            ui.color_edit_button_srgba_unmultiplied(&mut style.color);
+                            ui.add(Slider::new(&mut style.stroke, 1.0..=20.0).show_value(false)).on_hover_text("stroke");
+                        }
+                        None => {}
+                    }
+
+                    ui.separator();
+                    if ui.add_enabled(annotator.can_undo(), egui::Button::new("⟲")).on_hover_text("undo").clicked() {
+                        annotator.undo();
+                    }
+                    if ui.add_enabled(annotator.can_redo(), egui::Button::new("⟳")).on_hover_text("redo").clicked() {
+                        annotator.redo();
+                    }
+                });
+            });
+        }).response;
+        self.toolbar = Some(response.rect);
+    }
+
+    /// Whether the magnifier follows the pointer, which is while picking a point.
+    fn magnifier_visible(&self) -> bool {
+        self.magnifier.is_some()
+            && matches!(self.session.state(), AppState::Idle | AppState::Cropping(_) | AppState::Resizing(_, _, _))
+    }
+
+    /// The pointer on the desktop and the color under it.
+    fn pointer_pixel(&self, ctx: &Context) -> Option<(Pos2, [u8; 4])> {
+        let p = self.to_physical(ctx.pointer_hover_pos()?).floor();
+        Some((p, self.snapshot.pixel_at(p.into())?))
+    }
 
-            // TODO: size indicator (width x height)
+    /// Draw the zoomed pixels around the pointer, with its coordinates and color.
+    pub fn draw_magnifier(&self, ui: &mut Ui, ctx: &Context) {
+        let (Some((pixels, zoom)), Some(pointer)) = (self.magnifier, ctx.pointer_hover_pos()) else {
+            return;
+        };
+        if !self.magnifier_visible() {
+            return;
+        }
+        let p = self.to_physical(pointer);
+        let Some(screen) = self.snapshot.screen_at(p.into()) else {
+            return;
+        };
+        let Some((cx, cy)) = screen.to_image_pixel(p.into()) else {
+            return;
+        };
+        let color = screen.rgba_image.get_pixel(cx, cy).0;
+
+        let painter = ui.painter();
+        let font = FontId::monospace(12.0);
+        let lines = [
+            format!("{}, {}", p.x.floor(), p.y.floor()),
+            format!("{}  {} {} {}", hex_color(color), color[0], color[1], color[2]),
+        ].map(|line| painter.layout_no_wrap(line, font.clone(), Color32::WHITE));
+        let padding = 4.0;
+        let grid = pixels as f32 * zoom;
+        let text_height: f32 = lines.iter().map(|galley| galley.size().y).sum();
+        let size = Vec2::new(grid.max(lines.iter().map(|galley| galley.size().x).fold(0.0, f32::max)), grid + text_height) + Vec2::splat(padding * 2.0);
+        let pos = loupe_position(pointer, size, self.canvas(), 16.0);
+        painter.rect_filled(Rect::from_min_size(pos, size), Rounding::same(3.0), Color32::from_black_alpha(224));
+
+        // the pixels of the image (not the desktop units), so that nothing is skipped on dense screens
+        let origin = pos + Vec2::splat(padding);
+        let half = (pixels / 2) as i64;
+        for j in 0..pixels as i64 {
+            for i in 0..pixels as i64 {
+                let (ix, iy) = (cx as i64 + i - half, cy as i64 + j - half);
+                let fill = match (u32::try_from(ix), u32::try_from(iy)) {
+                    (Ok(ix), Ok(iy)) => screen.rgba_image.get_pixel_checked(ix, iy)
+                        .map_or(Color32::BLACK, |px| Color32::from_rgb(px[0], px[1], px[2])),
+                    _ => Color32::BLACK,
+                };
+                let cell = Rect::from_min_size(origin + Vec2::new(i as f32, j as f32) * zoom, Vec2::splat(zoom));
+                painter.rect_filled(cell, Rounding::ZERO, fill);
+            }
+        }
+        let center = Rect::from_min_size(origin + Vec2::splat(half as f32 * zoom), Vec2::splat(zoom));
+        painter.rect_stroke(center, Rounding::ZERO, Stroke::new(1.0, Color32::WHITE));
+        painter.rect_stroke(center.expand(1.0), Rounding::ZERO, Stroke::new(1.0, Color32::BLACK));
+
+        let mut y = origin.y + grid;
+        for galley in lines {
+            let height = galley.size().y;
+            painter.galley(Pos2::new(origin.x, y), galley, Color32::WHITE);
+            y += height;
+        }
+    }
+
+    /// Cover everything but the rect.
+    fn draw_mask(&self, ui: &mut Ui, rect: Rect) {
+        let canvas = self.canvas();
+        let tl_o = canvas.left_top();
+        let tr_o = canvas.right_top();
+        let br_o = canvas.right_bottom();
+        let bl_o = canvas.left_bottom();
+
+        let parts = [
+            Rect::from_two_pos(tl_o, rect.right_top()),
+            Rect::from_two_pos(tr_o, rect.right_bottom()),
+            Rect::from_two_pos(br_o, rect.left_bottom()),
+            Rect::from_two_pos(bl_o, rect.left_top()),
+        ];
+        for part in parts.into_iter() {
+            ui.painter().rect_filled(part, Rounding::ZERO, self.mask_color);
         }
     }
 
     pub fn update_cursor(&self, ctx: &Context) {
-        match self.app_state {
+        match self.session.state() {
             AppState::Cropped => {
                 // if there is a crop area, we need to update the
                 // cursor icon depending on the position relation
                 if let Some(p) = ctx.pointer_interact_pos() {
-                    ctx.output_mut(|o| o.cursor_icon = get_position_relation(self.crop_area.unwrap(), p).into());
+                    ctx.output_mut(|o| o.cursor_icon = self.session.relation_at(self.to_physical(p)).into());
                 }
             }
             AppState::Moving(_, _) => {
@@ -204,59 +551,116 @@ impl Helper {
         }
     }
 
-    pub fn handle_primary_pressed(&mut self, at: Option<Pos2>) {
-        if let Some(p) = at {
-            self.app_state = match self.app_state {
-                AppState::Idle => AppState::Cropping(p),
-                AppState::Cropped => {
-                    // we need to check the position relation of the
-                    // cursor to the crop area to determine the next state
-                    let crop_area = self.crop_area.unwrap();
-                    match get_position_relation(crop_area, p) {
-                        PositionRelation::Inside => AppState::Moving(crop_area, p),
-                        PositionRelation::Outside => AppState::Ignored,
-                        PositionRelation::Edge(code) => AppState::Resizing(crop_area, p, code)
-                    }
-                }
-                ref s @ _ => unreachable!("point pressed event should not happen in this app_state (state: {:?})", s),
-            };
+    /// Translate the input of this frame into session events.
+    pub fn handle_input(&mut self, ctx: &Context) {
+        // hover events
+        if let Some(p) = ctx.pointer_hover_pos() {
+            self.session.handle(SessionEvent::PointerMoved(self.to_physical(p)));
         }
-    }
 
-    pub fn handle_primary_down(&mut self, at: Option<Pos2>) {
-        if let Some(p) = at {
-            let constrained_p = p.clamp(Pos2::ZERO, self.max_point);
-            match self.app_state {
-                AppState::Cropping(p_start) => {
-                    self.crop_area = Some(Rect::from_two_pos(p_start, constrained_p));
-                }
-                AppState::Moving(crop_area, p_start) => {
-                    // translate the crop area by the difference between the current point and the start point
-                    self.crop_area = Some(apply_move(crop_area, p - p_start));
+        // primary pointer events, which draw annotations instead when there is a tool
+        let annotating = self.annotator.tool().is_some() && self.session.state() == AppState::Cropped;
+        let over_toolbar = ctx.pointer_interact_pos().zip(self.toolbar).is_some_and(|(p, rect)| rect.contains(p));
+        let pos = ctx.pointer_interact_pos().map(|p| self.to_physical(p));
+        if ctx.input(|i| i.pointer.primary_pressed()) {
+            if let Some(p) = pos.filter(|_| !over_toolbar) {
+                if annotating {
+                    self.annotator.pressed(p);
+                } else {
+                    self.session.handle(SessionEvent::PointerPressed(p));
                 }
-                AppState::Resizing(crop_area, p_start, code) => {
-                    // resize the crop area by the difference between the current point and the start point
-                    self.crop_area = Some(apply_resize(crop_area, p - p_start, code));
+            }
+        } else if ctx.input(|i| i.pointer.primary_down()) {
+            if let Some(p) = pos {
+                if annotating {
+                    self.annotator.dragged(p);
+                } else {
+                    self.session.handle(SessionEvent::PointerDragged(p));
                 }
-                AppState::Ignored => {
-                    // when the primary button is pressed outside the crop area.
-                    // we do nothing in this case.
+            }
+        } else if ctx.input(|i| i.pointer.primary_released()) {
+            if annotating {
+                self.annotator.released();
+            } else {
+                self.session.handle(SessionEvent::PointerReleased);
+            }
+        }
+
+        // typing takes all the keys, 'Enter' finishes the text and 'Escape' drops it
+        if self.annotator.is_typing() {
+            let events = ctx.input(|i| i.events.clone());
+            for event in events {
+                match event {
+                    Event::Text(text) => self.annotator.type_text(&text),
+                    Event::Key { key: Key::Backspace, pressed: true, .. } => self.annotator.backspace(),
+                    Event::Key { key: Key::Enter, pressed: true, .. } => self.annotator.commit_text(),
+                    Event::Key { key: Key::Escape, pressed: true, .. } => self.annotator.cancel_text(),
+                    _ => {}
                 }
-                ref s @ _ => unreachable!("point down event should not happen in this app_state (state: {:?})", s)
             }
+            return;
         }
-    }
 
-    pub fn handle_primary_released(&mut self) {
-        self.app_state = match self.app_state {
-            AppState::Cropping(_) | AppState::Moving(_, _) | AppState::Resizing(_, _, _) => AppState::Cropped,
-            AppState::Ignored => AppState::Cropped,
-            ref s @ _ => unreachable!("point released event should not happen in this app_state (state: {:?})", s),
+        // 'Ctrl + Z' undoes, 'Ctrl + Shift + Z' or 'Ctrl + Y' redoes, while annotating the cropped selection
+        if self.session.state() == AppState::Cropped {
+            let (undo, redo) = ctx.input(|i| (
+                i.modifiers.command && !i.modifiers.shift && i.key_pressed(Key::Z),
+                i.modifiers.command && (i.modifiers.shift && i.key_pressed(Key::Z) || i.key_pressed(Key::Y)),
+            ));
+            if undo {
+                self.annotator.undo();
+            }
+            if redo {
+                self.annotator.redo();
+            }
+        }
+
+        // keyboard events
+        let keys = ctx.input(|i| {
+            let mut keys = vec![];
+            if i.key_pressed(Key::Enter) {
+                keys.push(SessionKey::Enter);
+            }
+            if i.key_pressed(Key::Escape) {
+                keys.push(SessionKey::Escape);
+            }
+
+            // arrows: 1px (10px with Shift), move or resize (with Alt)
+            let step = if i.modifiers.shift { 10 } else { 1 };
+            for (key, dx, dy) in [(Key::ArrowLeft, -1, 0), (Key::ArrowRight, 1, 0), (Key::ArrowUp, 0, -1), (Key::ArrowDown, 0, 1)] {
+                if i.key_pressed(key) {
+                    keys.push(match i.modifiers.alt {
+                        true => SessionKey::Grow(dx * step, dy * step),
+                        false => SessionKey::Nudge(dx * step, dy * step),
+                    });
+                }
+            }
+
+            if i.modifiers.command && i.key_pressed(Key::A) {
+                keys.push(SessionKey::SelectMonitor);
+            }
+            if i.key_pressed(Key::Tab) {
+                keys.push(SessionKey::NextWindow { reverse: i.modifiers.shift });
+            }
+            if i.key_pressed(Key::Space) {
+                keys.push(SessionKey::Toggle);
+            }
+            keys
+        });
+        for key in keys {
+            self.session.handle(SessionEvent::KeyPressed(key));
         }
-    }
 
-    pub fn handle_enter_pressed(&self, ctx: &Context) {
-        ctx.send_viewport_cmd(ViewportCommand::Screenshot);
+        // color picking: 'C' copies the color under the pointer as HEX, 'Shift + C' as RGB
+        if self.magnifier_visible() && ctx.input(|i| i.key_pressed(Key::C) && !i.modifiers.command) {
+            if let Some((_, color)) = self.pointer_pixel(ctx) {
+                let text = match ctx.input(|i| i.modifiers.shift) {
+                    true => format!("rgb({}, {}, {})", color[0], color[1], color[2]),
+                    false => hex_color(color),
+                };
+                ctx.output_mut(|o| o.copied_text = text);
+            }
+        }
     }
 }
 
@@ -283,33 +687,33 @@ impl eframe::App for CropApp {
         egui::CentralPanel::default()
             .frame(Frame::none().fill(Color32::WHITE))
             .show(ctx, |ui| {
+                self.helper.update_scale(ctx);
+
                 // draw ui
                 self.helper.draw_screens(ui);
                 self.helper.draw_crop(ui);
-                // TODO: draw operation UI
+                self.helper.update_previews(ctx);
+                self.helper.draw_annotations(ui);
+                self.helper.draw_magnifier(ui, ctx);
+                self.helper.draw_toolbar(ctx);
 
                 // update cursor icon
                 self.helper.update_cursor(ctx);
 
-                // interactive cropping - primary pointer events
-                if ctx.input(|i| i.pointer.primary_pressed()) {
-                    let pos = ctx.pointer_interact_pos();
-                    self.helper.handle_primary_pressed(pos);
-                } else if ctx.input(|i| i.pointer.primary_down()) {
-                    let pos = ctx.pointer_interact_pos();
-                    self.helper.handle_primary_down(pos);
-                } else if ctx.input(|i| i.pointer.primary_released()) {
-                    self.helper.handle_primary_released();
-                }
+                // interactive cropping
+                self.helper.handle_input(ctx);
 
-                // exit trigger - press 'Enter' key
-                if ctx.input(|i| i.key_pressed(Key::Enter)) {
-                    self.helper.handle_enter_pressed(ctx);
-                }
-
-                // exit condition - press 'Esc' key
-                if ctx.input(|i| i.key_pressed(Key::Escape)) {
-                    ctx.send_viewport_cmd(ViewportCommand::Close);
+                match self.helper.session.outcome() {
+                    // exit trigger - press 'Enter' key, fill in 'out' and exit
+                    SessionOutcome::Confirmed(_) => {
+                        *self.out.borrow_mut() = self.helper.crop();
+                        ctx.send_viewport_cmd(ViewportCommand::Close);
+                    }
+                    // exit condition - press 'Esc' key
+                    SessionOutcome::Cancelled => {
+                        ctx.send_viewport_cmd(ViewportCommand::Close);
+                    }
+                    SessionOutcome::Pending => {}
                 }
                 // exit condition - lose focus
                 if self.ready {
@@ -321,27 +725,86 @@ impl eframe::App for CropApp {
                         self.ready = true;
                     }
                 }
-                // exit condition - screenshot event
-                if let Some(crop_area) = ctx.input(|i| {
-                    for event in &i.raw.events {
-                        if let Event::Screenshot { image, .. } = event {
-                            let ppp = i.pixels_per_point;
-                            return Some(image.region(&self.helper.crop_area.unwrap(), Some(ppp)));
-                        }
-                    }
-                    None
-                }) {
-                    // generate results and fill in 'out'
-                    let result = RgbaImage::from_raw(
-                        crop_area.width() as u32,
-                        crop_area.height() as u32,
-                        crop_area.as_raw().to_owned(),
-                    ).unwrap();
-                    *self.out.borrow_mut() = Some(result);
-
-                    // then exit
-                    ctx.send_viewport_cmd(ViewportCommand::Close);
-                }
             });
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod unit_test {
+    use crate::canonical::ScreenInfo;
+    use super::*;
+
+    #[test]
+    fn helper_mapping_test() {
+        // a 100% primary screen on the left of a 200% one, in pixels
+        let screen = |name: &str, xywh: (i32, i32, u32, u32), sf: f32| ScreenInfo {
+            name: name.into(),
+            is_primary: xywh.0 == 0,
+            xywh,
+            sf,
+            rgba_image: RgbaImage::new(xywh.2, xywh.3),
+        };
+        let snapshot = Snapshot::new(vec![screen("left", (0, 0, 100, 100), 1.0), screen("right", (100, 0, 200, 200), 2.0)], vec![]).unwrap();
+        let mut helper = Helper::new(snapshot, CropperConfig::default());
+
+        // opened with the scale of the primary screen
+        assert_eq!(helper.canvas(), Rect::from_min_max(Pos2::ZERO, Pos2::new(300.0, 200.0)));
+        assert_eq!(helper.to_ui_pos(Pos2::new(250.0, 150.0)), Pos2::new(250.0, 150.0));
+
+        // egui renders the whole window at 2x once it is on the 200% screen, a point is 2 pixels on both screens
+        assert!(helper.set_pixels_per_point(2.0));
+        assert!(!helper.set_pixels_per_point(2.0));
+        assert_eq!(helper.canvas(), Rect::from_min_max(Pos2::ZERO, Pos2::new(150.0, 100.0)));
+        assert_eq!(helper.to_ui_pos(Pos2::new(50.0, 50.0)), Pos2::new(25.0, 25.0));
+        assert_eq!(helper.to_ui_pos(Pos2::new(250.0, 150.0)), Pos2::new(125.0, 75.0));
+        assert_eq!(helper.to_ui(PhysicalRect::from_xywh((90, 0, 20, 10))), Rect::from_min_max(Pos2::new(45.0, 0.0), Pos2::new(55.0, 5.0)));
+        for pos in [Pos2::new(50.0, 50.0), Pos2::new(99.5, 0.0), Pos2::new(100.0, 20.0), Pos2::new(250.0, 150.0)] {
+            assert_eq!(helper.to_physical(helper.to_ui_pos(pos)), pos);
+        }
+    }
+
+    #[test]
+    fn label_position_test() {
+        let canvas = Rect::from_min_max(Pos2::ZERO, Pos2::new(1000.0, 800.0));
+        let label = Vec2::new(80.0, 20.0);
+        let selection = |x1: f32, y1: f32, x2: f32, y2: f32| Rect::from_min_max(Pos2::new(x1, y1), Pos2::new(x2, y2));
+
+        // above the top-left corner
+        assert_eq!(label_position(selection(100.0, 100.0, 300.0, 300.0), label, canvas, 4.0), Pos2::new(100.0, 76.0));
+        // below, at the top edge of the desktop
+        assert_eq!(label_position(selection(100.0, 10.0, 300.0, 300.0), label, canvas, 4.0), Pos2::new(100.0, 304.0));
+        // inside, when the selection fills the height
+        assert_eq!(label_position(selection(100.0, 10.0, 300.0, 790.0), label, canvas, 4.0), Pos2::new(100.0, 14.0));
+        // kept inside at the right edge
+        assert_eq!(label_position(selection(950.0, 100.0, 1000.0, 300.0), label, canvas, 4.0), Pos2::new(920.0, 76.0));
+    }
+
+    #[test]
+    fn toolbar_position_test() {
+        let canvas = Rect::from_min_max(Pos2::ZERO, Pos2::new(1000.0, 800.0));
+        let toolbar = Vec2::new(400.0, 30.0);
+        let selection = |x1: f32, y1: f32, x2: f32, y2: f32| Rect::from_min_max(Pos2::new(x1, y1), Pos2::new(x2, y2));
+
+        // below the bottom-left corner
+        assert_eq!(toolbar_position(selection(100.0, 100.0, 300.0, 300.0), toolbar, canvas, 8.0), Pos2::new(100.0, 308.0));
+        // above, at the bottom edge of the desktop
+        assert_eq!(toolbar_position(selection(100.0, 100.0, 300.0, 790.0), toolbar, canvas, 8.0), Pos2::new(100.0, 62.0));
+        // inside, when the selection fills the height, and kept inside at the right edge
+        assert_eq!(toolbar_position(selection(800.0, 0.0, 1000.0, 800.0), toolbar, canvas, 8.0), Pos2::new(600.0, 762.0));
+    }
+
+    #[test]
+    fn loupe_position_test() {
+        let canvas = Rect::from_min_max(Pos2::ZERO, Pos2::new(1000.0, 800.0));
+        let loupe = Vec2::new(120.0, 150.0);
+
+        // below and to the right
+        assert_eq!(loupe_position(Pos2::new(100.0, 100.0), loupe, canvas, 16.0), Pos2::new(116.0, 116.0));
+        // flipped at the right edge
+        assert_eq!(loupe_position(Pos2::new(900.0, 100.0), loupe, canvas, 16.0), Pos2::new(764.0, 116.0));
+        // flipped at the bottom-right corner
+        assert_eq!(loupe_position(Pos2::new(900.0, 700.0), loupe, canvas, 16.0), Pos2::new(764.0, 534.0));
+
+        assert_eq!(hex_color([255, 8, 160, 0]), "#FF08A0");
+    }
+}
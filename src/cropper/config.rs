@@ -1,10 +1,44 @@
 /// config for cropper
+#[derive(Clone, Debug)]
 pub struct CropperConfig {
     /// whether to automatically bounding the application window when the mouse passes over it
     pub auto_bounding: bool,
 
     /// mask color, in RGBA format. Default to [0, 0, 0, 128]
     pub mask_color: [u8; 4],
+
+    /// side length of the resize handles, in points. Default to 8.0
+    pub handle_size: f32,
+
+    /// color of the resize handles, in RGBA format. Default to [255, 255, 255, 255]
+    pub handle_color: [u8; 4],
+
+    /// how far (in points) from an edge of the selection the mouse still grabs it. Default to 4.0
+    pub edge_tolerance: f32,
+
+    /// the smallest width and height (in physical pixels) the selection can be resized to. Default to [1, 1]
+    pub min_size: [u32; 2],
+
+    /// whether to show the magnifier next to the mouse while selecting. Default to true
+    pub magnifier: bool,
+
+    /// number of pixels across the magnifier, odd so that there is a center pixel. Default to 15
+    pub magnifier_pixels: u32,
+
+    /// side length (in points) of each pixel in the magnifier. Default to 8.0
+    pub magnifier_zoom: f32,
+
+    /// initial color of the annotation tools, in RGBA format. Default to [255, 0, 0, 255]
+    pub annotation_color: [u8; 4],
+
+    /// initial stroke width (in physical pixels) of the annotation tools. Default to 3.0
+    pub annotation_stroke: f32,
+
+    /// initial block size (in physical pixels) of the pixelate tool, at least 2. Default to 12
+    pub pixelate_block: u32,
+
+    /// initial strength (sigma, in physical pixels) of the blur tool, at least 2. Default to 8.0
+    pub blur_sigma: f32,
 }
 
 impl Default for CropperConfig {
@@ -12,12 +46,23 @@ impl Default for CropperConfig {
         CropperConfig {
             auto_bounding: false,
             mask_color: [0, 0, 0, 128],
+            handle_size: 8.0,
+            handle_color: [255, 255, 255, 255],
+            edge_tolerance: 4.0,
+            min_size: [1, 1],
+            magnifier: true,
+            magnifier_pixels: 15,
+            magnifier_zoom: 8.0,
+            annotation_color: [255, 0, 0, 255],
+            annotation_stroke: 3.0,
+            pixelate_block: 12,
+            blur_sigma: 8.0,
         }
     }
 }
 
 impl CropperConfig {
-    pub fn get_mask_color(&self) -> egui::Color32 {
+    pub(crate) fn get_mask_color(&self) -> egui::Color32 {
         egui::Color32::from_rgba_premultiplied(
             self.mask_color[0],
             self.mask_color[1],
@@ -25,4 +70,13 @@ impl CropperConfig {
             self.mask_color[3],
         )
     }
+
+    pub(crate) fn get_handle_color(&self) -> egui::Color32 {
+        egui::Color32::from_rgba_premultiplied(
+            self.handle_color[0],
+            self.handle_color[1],
+            self.handle_color[2],
+            self.handle_color[3],
+        )
+    }
 }
\ No newline at end of file
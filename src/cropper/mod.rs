@@ -1,5 +1,7 @@
+mod annotate;
 mod app;
 mod config;
+mod session;
 
 use std::cell::RefCell;
 use std::rc::Rc;
@@ -7,41 +9,42 @@ use app::{CropApp};
 pub use config::CropperConfig;
 use egui::ViewportBuilder;
 use image::RgbaImage;
-use crate::snapper::Snapper;
+use crate::canonical::{CaptureError, DesktopSpace, LogicalRect, Snapshot};
 
 pub struct Cropper;
 
 impl Cropper {
-    /// Take a snapshot and crop it with interactive UI
-    pub fn exec(cropper_config: CropperConfig) -> Result<Option<RgbaImage>, String> {
-        let snapshot = Snapper::take_snapshot(cropper_config.auto_bounding)?;
-
-        let (x, y, w, h) = snapshot.xywh;
+    /// Crop the given snapshot with interactive UI, auto-bounding uses the apps of the snapshot
+    pub fn exec_on(snapshot: Snapshot, cropper_config: CropperConfig) -> Result<Option<RgbaImage>, CaptureError> {
+        // the viewport is given in points, which the window is created with the scale of the primary screen for,
+        // it is placed again if it ends up with another scale
+        let LogicalRect { min, max } = DesktopSpace::native(&snapshot.screens).viewport();
         let option = eframe::NativeOptions {
             viewport: ViewportBuilder::default()
                 .with_taskbar(false)
                 .with_decorations(false)
                 .with_always_on_top()
-                .with_position([x as f32, y as f32])
-                .with_inner_size([w as f32, h as f32]),
+                .with_position([min.x, min.y])
+                .with_inner_size([max.x - min.x, max.y - min.y]),
             ..Default::default()
         };
 
         let result: Rc<RefCell<Option<RgbaImage>>> = Rc::new(RefCell::new(None));
+        let out = result.clone();
         eframe::run_native(
             "Capture",
             option,
-            Box::new(|cc| {
+            Box::new(move |cc| {
                 egui_extras::install_image_loaders(&cc.egui_ctx);
-                Box::new(CropApp::new(snapshot, cropper_config, result.clone()))
+                Box::new(CropApp::new(snapshot, cropper_config, out))
             }),
-        ).unwrap();
+        ).map_err(|err| CaptureError::Ui(err.to_string()))?;
 
         // we make sure that 'result' only has a reference count of 1 at this point,
         // so use 'Rc::try_unwrap' to take ownership and 'into_inner' to get the value inside
         // Ok(Rc::try_unwrap(result).unwrap().into_inner())
 
         // use 'Rc::unwrap_or_clone' instead of 'Rc::try_unwrap' to ensure success
-        Ok(Rc::unwrap_or_clone(result).unwrap().into_inner())
+        Ok(Rc::unwrap_or_clone(result).into_inner())
     }
 }
\ No newline at end of file
@@ -0,0 +1,853 @@
+use egui::{Pos2, Rect, Vec2};
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum PositionRelation {
+    Inside,
+    Outside,
+    /// we use something like one-hot encoding to represent the position relation.
+    ///
+    /// THAT IS:
+    ///
+    /// | Position     | Code |
+    /// |--------------|------|
+    /// | top-left     | 5    |
+    /// | top-right    | 3    |
+    /// | bottom-right | 8    |
+    /// | bottom-left  | 10   |
+    /// | top          | 1    |
+    /// | right        | 2    |
+    /// | bottom       | 6    |
+    /// | left         | 4    |
+    Edge(u8),
+}
+
+/// The areas of the eight resize handles: squares of `size` centered on the corners and the
+/// middles of the edges, with the code of the edge(s) each one moves. Corners come first.
+pub fn handle_rects(rect: Rect, size: f32) -> [(u8, Rect); 8] {
+    let square = |center: Pos2| Rect::from_center_size(center, Vec2::splat(size));
+    [
+        (5, square(rect.left_top())),
+        (3, square(rect.right_top())),
+        (8, square(rect.right_bottom())),
+        (10, square(rect.left_bottom())),
+        (1, square(rect.center_top())),
+        (2, square(rect.right_center())),
+        (6, square(rect.center_bottom())),
+        (4, square(rect.left_center())),
+    ]
+}
+
+/// Position relation of the point to the rect.
+///
+/// The pointer grabs an edge within `tolerance` of it, or in the handle areas (see [`handle_rects`]).
+/// Corners take priority over edges, so a small rect can still be resized diagonally.
+pub fn get_position_relation(bounding: Rect, point: Pos2, handle_size: f32, tolerance: f32) -> PositionRelation {
+    let Pos2 { x: px, y: py } = point;
+    let Rect { min: Pos2 { x: bxl, y: byt }, max: Pos2 { x: bxr, y: byb } } = bounding;
+    let handles = handle_rects(bounding, handle_size);
+
+    // which edge the point is close to on each axis, the closer one wins if both are
+    let within_y = py >= byt - tolerance && py <= byb + tolerance;
+    let within_x = px >= bxl - tolerance && px <= bxr + tolerance;
+    let nearest = |p: f32, low: f32, high: f32, low_code: u8, high_code: u8| {
+        let (d_low, d_high) = ((p - low).abs(), (p - high).abs());
+        if d_low <= tolerance && d_low <= d_high {
+            low_code
+        } else if d_high <= tolerance {
+            high_code
+        } else {
+            0
+        }
+    };
+    let x_code = if within_y { nearest(px, bxl, bxr, 4, 2) } else { 0 };
+    let y_code = if within_x { nearest(py, byt, byb, 1, 6) } else { 0 };
+
+    // corners
+    if let Some((code, _)) = handles[..4].iter().find(|(_, area)| area.contains(point)) {
+        return PositionRelation::Edge(*code);
+    }
+    if x_code != 0 && y_code != 0 {
+        return PositionRelation::Edge(x_code + y_code);
+    }
+
+    // edges
+    if let Some((code, _)) = handles[4..].iter().find(|(_, area)| area.contains(point)) {
+        return PositionRelation::Edge(*code);
+    }
+    if x_code + y_code != 0 {
+        return PositionRelation::Edge(x_code + y_code);
+    }
+
+    if px > bxl && px < bxr && py > byt && py < byb {
+        PositionRelation::Inside
+    } else {
+        PositionRelation::Outside
+    }
+}
+
+/// Translate the rect, but keep it inside the bounds (as long as it fits) without changing its size.
+pub fn apply_move(rect: Rect, modify: Vec2, bounds: Rect) -> Rect {
+    let moved = rect.translate(modify);
+    let x = moved.min.x.min(bounds.max.x - rect.width()).max(bounds.min.x);
+    let y = moved.min.y.min(bounds.max.y - rect.height()).max(bounds.min.y);
+    Rect::from_min_size(Pos2::new(x, y), rect.size())
+}
+
+/// Resize the rect by moving the edge(s) given by the code, return the new rect and the code of the edge(s)
+/// under the pointer afterwards.
+///
+/// The moving edges stay inside the bounds. When one of them crosses the opposite edge, the rect flips
+/// and the code is handed off to the opposite edge, e.g. dragging the left edge past the right edge goes
+/// on as dragging the right edge. The moving edges keep the rect at least `min_size`.
+///
+/// An unknown code leaves the rect untouched.
+pub fn apply_resize(rect: Rect, modify: Vec2, code: u8, bounds: Rect, min_size: Vec2) -> (Rect, u8) {
+    // (left, right, top, bottom) of the moving edges
+    let (mut left, mut right, mut top, mut bottom) = match code {
+        5 => (true, false, true, false),
+        3 => (false, true, true, false),
+        8 => (false, true, false, true),
+        10 => (true, false, false, true),
+        1 => (false, false, true, false),
+        2 => (false, true, false, false),
+        6 => (false, false, false, true),
+        4 => (true, false, false, false),
+        _ => return (rect, code),
+    };
+
+    let Rect { min: Pos2 { x: mut x1, y: mut y1 }, max: Pos2 { x: mut x2, y: mut y2 } } = rect;
+    if left {
+        x1 = (x1 + modify.x).clamp(bounds.min.x, bounds.max.x);
+    }
+    if right {
+        x2 = (x2 + modify.x).clamp(bounds.min.x, bounds.max.x);
+    }
+    if top {
+        y1 = (y1 + modify.y).clamp(bounds.min.y, bounds.max.y);
+    }
+    if bottom {
+        y2 = (y2 + modify.y).clamp(bounds.min.y, bounds.max.y);
+    }
+
+    // flip
+    if x1 > x2 {
+        (x1, x2) = (x2, x1);
+        (left, right) = (right, left);
+    }
+    if y1 > y2 {
+        (y1, y2) = (y2, y1);
+        (top, bottom) = (bottom, top);
+    }
+
+    // grow the moving edge away from the fixed one, or back from the bounds
+    if left && x2 - x1 < min_size.x {
+        x1 = (x2 - min_size.x).max(bounds.min.x);
+        x2 = x2.max(x1 + min_size.x);
+    }
+    if right && x2 - x1 < min_size.x {
+        x2 = (x1 + min_size.x).min(bounds.max.x);
+        x1 = x1.min(x2 - min_size.x);
+    }
+    if top && y2 - y1 < min_size.y {
+        y1 = (y2 - min_size.y).max(bounds.min.y);
+        y2 = y2.max(y1 + min_size.y);
+    }
+    if bottom && y2 - y1 < min_size.y {
+        y2 = (y1 + min_size.y).min(bounds.max.y);
+        y1 = y1.min(y2 - min_size.y);
+    }
+
+    let code = left as u8 * 4 + right as u8 * 2 + top as u8 + bottom as u8 * 6;
+    (Rect::from_min_max(Pos2::new(x1, y1), Pos2::new(x2, y2)), code)
+}
+
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum AppState {
+    /// primary button is up, no crop area
+    Idle,
+    /// primary button is down, crop area is updating.
+    /// - (start point)
+    Cropping(Pos2),
+    /// primary button is up, crop area is fixed
+    Cropped,
+    /// primary button is down/released, but we ignore the event and do nothing
+    /// this case happens when the primary button is pressed outside the crop area
+    Ignored,
+    /// primary button is down, crop area is moving
+    /// - (crop area, start point)
+    Moving(Rect, Pos2),
+    /// primary button is down, crop area is resizing
+    /// - (crop area, start point, code)
+    Resizing(Rect, Pos2, u8),
+}
+
+/// Keys that the session reacts to.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub enum SessionKey {
+    Enter,
+    Escape,
+    /// move the selection by the offset, in pixels (arrow keys)
+    Nudge(i32, i32),
+    /// move the right and bottom edges of the selection by the offset, in pixels (Alt + arrow keys)
+    Grow(i32, i32),
+    /// select the monitor under the pointer (Ctrl + A)
+    SelectMonitor,
+    /// select the next detected window, or the previous one if reversed (Tab)
+    NextWindow { reverse: bool },
+    /// start a selection at the pointer, or end the current one (Space)
+    Toggle,
+}
+
+/// Abstract input events, decoupled from the UI framework.
+///
+/// All points are in the physical coordinates (see [`PhysicalPos`](crate::canonical::PhysicalPos)),
+/// so the crop area is measured in the pixels of the desktop, whatever the scale factor of the UI is.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SessionEvent {
+    /// primary button goes down at the point
+    PointerPressed(Pos2),
+    /// primary button is (still) down and the pointer is at the point
+    PointerDragged(Pos2),
+    /// primary button goes up
+    PointerReleased,
+    /// the pointer is at the point, no matter the primary button is down or not
+    PointerMoved(Pos2),
+    KeyPressed(SessionKey),
+}
+
+/// What the session ends up with.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SessionOutcome {
+    /// still cropping
+    Pending,
+    /// the crop area is confirmed (by pressing 'Enter')
+    Confirmed(Rect),
+    /// the session is aborted (by pressing 'Esc')
+    Cancelled,
+}
+
+/// The state machine of the interactive cropping, without any UI.
+///
+/// Events can come in any order: the unexpected ones are either ignored or
+/// treated as if the missing events had happened, it never panics.
+#[derive(Debug)]
+pub struct CropSession {
+    /// bounding box of the crop canvas
+    bounds: Rect,
+
+    /// bounding boxes of the windows that can be picked, from the topmost to the bottommost
+    windows: Vec<Rect>,
+    /// the window under the pointer, only tracked in the `Idle` state
+    hovered: Option<Rect>,
+    /// index of the window selected by the keyboard
+    window_index: Option<usize>,
+
+    /// bounding boxes of the monitors
+    monitors: Vec<Rect>,
+    /// the last known position of the pointer
+    pointer: Option<Pos2>,
+
+    /// side length of the resize handles
+    handle_size: f32,
+    /// how far from an edge the pointer still grabs it
+    tolerance: f32,
+    /// the smallest size resizing can shrink the crop area to
+    min_size: Vec2,
+
+    /// state of the session
+    state: AppState,
+
+    /// rect of the crop area
+    crop_area: Option<Rect>,
+
+    outcome: SessionOutcome,
+}
+
+impl CropSession {
+    pub fn new(bounds: Rect) -> CropSession {
+        CropSession {
+            bounds,
+            windows: vec![],
+            hovered: None,
+            window_index: None,
+            monitors: vec![],
+            pointer: None,
+            handle_size: 0.0,
+            tolerance: 0.0,
+            min_size: Vec2::ZERO,
+            state: AppState::Idle,
+            crop_area: None,
+            outcome: SessionOutcome::Pending,
+        }
+    }
+
+    /// Enable auto-bounding with the given windows, from the topmost to the bottommost.
+    ///
+    /// They are clipped to the canvas, the ones outside of it are dropped.
+    pub fn with_windows(mut self, windows: Vec<Rect>) -> CropSession {
+        self.windows = windows.into_iter()
+            .map(|rect| rect.intersect(self.bounds))
+            .filter(|rect| rect.is_positive())
+            .collect();
+        self
+    }
+
+    /// Set the monitors, which can be selected as a whole.
+    pub fn with_monitors(mut self, monitors: Vec<Rect>) -> CropSession {
+        self.monitors = monitors;
+        self
+    }
+
+    /// Set the smallest size of the crop area when resizing.
+    pub fn with_min_size(mut self, min_size: Vec2) -> CropSession {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Set the side length of the resize handles and the edge tolerance, which follow the scale factor of the UI.
+    pub fn set_hit_area(&mut self, handle_size: f32, tolerance: f32) {
+        self.handle_size = handle_size;
+        self.tolerance = tolerance;
+    }
+
+    pub fn state(&self) -> AppState {
+        self.state
+    }
+
+    /// The current crop area, if any.
+    pub fn selection(&self) -> Option<Rect> {
+        self.crop_area
+    }
+
+    /// The window to highlight, if any.
+    pub fn hovered(&self) -> Option<Rect> {
+        self.hovered
+    }
+
+    /// The topmost window at the point.
+    fn window_at(&self, point: Pos2) -> Option<Rect> {
+        self.windows.iter().find(|rect| rect.contains(point)).copied()
+    }
+
+    pub fn outcome(&self) -> SessionOutcome {
+        self.outcome
+    }
+
+    /// Position relation of the point to the current crop area.
+    pub fn relation_at(&self, point: Pos2) -> PositionRelation {
+        match self.crop_area {
+            Some(rect) => get_position_relation(rect, point, self.handle_size, self.tolerance),
+            None => PositionRelation::Outside,
+        }
+    }
+
+    /// Feed an event into the session, return the outcome after it.
+    pub fn handle(&mut self, event: SessionEvent) -> SessionOutcome {
+        // once finished, the session no longer changes
+        if self.outcome != SessionOutcome::Pending {
+            return self.outcome;
+        }
+
+        match event {
+            SessionEvent::PointerPressed(p) => self.on_pressed(p),
+            SessionEvent::PointerDragged(p) => self.on_dragged(p),
+            SessionEvent::PointerReleased => self.on_released(),
+            SessionEvent::PointerMoved(p) => self.on_moved(p),
+            SessionEvent::KeyPressed(key) => self.on_key(key),
+        }
+
+        self.outcome
+    }
+
+    fn on_pressed(&mut self, p: Pos2) {
+        // the release of the last press is missed, finish it first
+        if !matches!(self.state, AppState::Idle | AppState::Cropped) {
+            self.on_released();
+        }
+
+        self.hovered = None;
+        self.state = match self.crop_area {
+            None => AppState::Cropping(p),
+            Some(crop_area) => {
+                // we need to check the position relation of the
+                // cursor to the crop area to determine the next state
+                match get_position_relation(crop_area, p, self.handle_size, self.tolerance) {
+                    PositionRelation::Inside => AppState::Moving(crop_area, p),
+                    PositionRelation::Outside => AppState::Ignored,
+                    PositionRelation::Edge(code) => AppState::Resizing(crop_area, p, code)
+                }
+            }
+        };
+    }
+
+    fn on_dragged(&mut self, p: Pos2) {
+        let constrained_p = p.clamp(self.bounds.min, self.bounds.max);
+        match self.state {
+            AppState::Cropping(p_start) => {
+                self.crop_area = Some(Rect::from_two_pos(p_start, constrained_p));
+            }
+            AppState::Moving(crop_area, p_start) => {
+                // translate the crop area by the difference between the current point and the start point
+                self.crop_area = Some(apply_move(crop_area, p - p_start, self.bounds));
+            }
+            AppState::Resizing(crop_area, p_start, code) => {
+                // resize the crop area by the difference between the current point and the start point
+                let (rect, new_code) = apply_resize(crop_area, p - p_start, code, self.bounds, self.min_size);
+                self.crop_area = Some(rect);
+                // flipped, go on from here with the opposite edge
+                if new_code != code {
+                    self.state = AppState::Resizing(rect, p, new_code);
+                }
+            }
+            AppState::Ignored => {
+                // when the primary button is pressed outside the crop area.
+                // we do nothing in this case.
+            }
+            AppState::Idle | AppState::Cropped => {
+                // the press is missed, there is nothing to drag
+            }
+        }
+    }
+
+    fn on_moved(&mut self, p: Pos2) {
+        self.pointer = Some(p);
+        self.hovered = match self.state {
+            AppState::Idle => self.window_at(p),
+            _ => None,
+        };
+
+        // a selection started by the keyboard follows the pointer without any button down
+        if let AppState::Cropping(p_start) = self.state {
+            self.crop_area = Some(Rect::from_two_pos(p_start, p.clamp(self.bounds.min, self.bounds.max)));
+        }
+    }
+
+    /// Replace the crop area with the rect, as if it were just cropped.
+    fn select(&mut self, rect: Rect) {
+        self.crop_area = Some(rect);
+        self.hovered = None;
+        self.state = AppState::Cropped;
+    }
+
+    fn on_released(&mut self) {
+        // a click without dragging leaves an empty crop area, drop it
+        if self.crop_area.is_some_and(|rect| rect.area() <= 0.0) {
+            self.crop_area = None;
+        }
+        // and picks the window under the pointer instead, if any
+        if let (AppState::Cropping(p_start), None) = (self.state, self.crop_area) {
+            self.crop_area = self.window_at(p_start);
+        }
+
+        self.state = match self.crop_area {
+            Some(_) => AppState::Cropped,
+            None => AppState::Idle,
+        };
+    }
+
+    fn on_key(&mut self, key: SessionKey) {
+        match key {
+            SessionKey::Enter => {
+                if let Some(rect) = self.crop_area {
+                    self.outcome = SessionOutcome::Confirmed(rect);
+                }
+            }
+            SessionKey::Escape => {
+                self.outcome = SessionOutcome::Cancelled;
+            }
+            SessionKey::Toggle => match (self.state, self.pointer) {
+                (AppState::Cropping(_), _) => self.on_released(),
+                (AppState::Idle | AppState::Cropped, Some(p)) => {
+                    self.crop_area = None;
+                    self.hovered = None;
+                    self.state = AppState::Cropping(p.clamp(self.bounds.min, self.bounds.max));
+                }
+                _ => {}
+            }
+            // the other keys only edit a settled selection, not one being dragged
+            _ if !matches!(self.state, AppState::Idle | AppState::Cropped) => {}
+            SessionKey::Nudge(dx, dy) => {
+                if let Some(rect) = self.crop_area {
+                    self.crop_area = Some(apply_move(rect, Vec2::new(dx as f32, dy as f32), self.bounds));
+                }
+            }
+            SessionKey::Grow(dx, dy) => {
+                // unlike dragging, the edges stop at the minimum size instead of flipping over
+                if let Some(rect) = self.crop_area {
+                    let max = (rect.max + Vec2::new(dx as f32, dy as f32))
+                        .max(rect.min + self.min_size)
+                        .min(self.bounds.max);
+                    self.crop_area = Some(Rect::from_min_max(rect.min, max));
+                }
+            }
+            SessionKey::SelectMonitor => {
+                let monitor = self.pointer
+                    .and_then(|p| self.monitors.iter().find(|m| m.contains(p)))
+                    .or(self.monitors.first());
+                if let Some(&monitor) = monitor {
+                    self.select(monitor);
+                }
+            }
+            SessionKey::NextWindow { reverse } => {
+                let count = self.windows.len();
+                if count > 0 {
+                    let index = match (self.window_index, reverse) {
+                        (None, false) => 0,
+                        (None, true) => count - 1,
+                        (Some(i), false) => (i + 1) % count,
+                        (Some(i), true) => (i + count - 1) % count,
+                    };
+                    self.window_index = Some(index);
+                    self.select(self.windows[index]);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+
+    fn session() -> CropSession {
+        CropSession::new(Rect::from_min_max(Pos2::ZERO, Pos2::new(1000.0, 800.0)))
+    }
+
+    fn drag(session: &mut CropSession, from: Pos2, to: Pos2) {
+        session.handle(SessionEvent::PointerPressed(from));
+        session.handle(SessionEvent::PointerDragged(to));
+        session.handle(SessionEvent::PointerReleased);
+    }
+
+    #[test]
+    fn crop_move_resize_test() {
+        let mut s = session();
+        drag(&mut s, Pos2::new(300.0, 200.0), Pos2::new(100.0, 100.0));
+        assert_eq!(s.state(), AppState::Cropped);
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(100.0, 100.0), Pos2::new(300.0, 200.0))));
+
+        // move
+        drag(&mut s, Pos2::new(150.0, 150.0), Pos2::new(160.0, 170.0));
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(110.0, 120.0), Pos2::new(310.0, 220.0))));
+
+        // resize by the bottom-right corner
+        drag(&mut s, Pos2::new(310.0, 220.0), Pos2::new(400.0, 300.0));
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(110.0, 120.0), Pos2::new(400.0, 300.0))));
+
+        // press outside is ignored
+        drag(&mut s, Pos2::new(900.0, 700.0), Pos2::new(0.0, 0.0));
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(110.0, 120.0), Pos2::new(400.0, 300.0))));
+
+        let outcome = s.handle(SessionEvent::KeyPressed(SessionKey::Enter));
+        assert_eq!(outcome, SessionOutcome::Confirmed(Rect::from_min_max(Pos2::new(110.0, 120.0), Pos2::new(400.0, 300.0))));
+    }
+
+    #[test]
+    fn edge_code_test() {
+        // the codes are the sums of the one-hot codes of the edges
+        let (top, right, left, bottom) = (1, 2, 4, 6);
+        let codes = [left + top, right + top, right + bottom, left + bottom, top, right, bottom, left];
+        assert_eq!(codes, [5, 3, 8, 10, 1, 2, 6, 4]);
+        let handles: Vec<u8> = handle_rects(Rect::ZERO, 1.0).iter().map(|(code, _)| *code).collect();
+        assert_eq!(handles, codes);
+
+        // every code resizes the edge(s) it stands for, and only them
+        let rect = Rect::from_min_max(Pos2::new(10.0, 10.0), Pos2::new(20.0, 20.0));
+        let bounds = Rect::from_min_max(Pos2::ZERO, Pos2::new(100.0, 100.0));
+        let d = Vec2::new(1.0, 2.0);
+        for code in codes {
+            let (resized, new_code) = apply_resize(rect, d, code, bounds, Vec2::ZERO);
+            assert_eq!(new_code, code);
+            assert_eq!(resized.left(), if matches!(code, 4 | 5 | 10) { 11.0 } else { 10.0 }, "code {}", code);
+            assert_eq!(resized.right(), if matches!(code, 2 | 3 | 8) { 21.0 } else { 20.0 }, "code {}", code);
+            assert_eq!(resized.top(), if matches!(code, 1 | 3 | 5) { 12.0 } else { 10.0 }, "code {}", code);
+            assert_eq!(resized.bottom(), if matches!(code, 6 | 8 | 10) { 22.0 } else { 20.0 }, "code {}", code);
+        }
+        assert_eq!(apply_resize(rect, d, 7, bounds, Vec2::ZERO), (rect, 7));
+    }
+
+    #[test]
+    fn edge_tolerance_test() {
+        use PositionRelation::{Edge, Inside, Outside};
+        let rect = Rect::from_min_max(Pos2::new(20.0, 20.0), Pos2::new(60.0, 50.0));
+        // a rect smaller than the tolerance zones, which overlap
+        let tiny = Rect::from_min_max(Pos2::new(20.0, 20.0), Pos2::new(23.0, 23.0));
+        let cases = [
+            // each edge, at the tolerance on both sides, and just beyond it
+            (rect, 2.0, (40.0, 18.0), Edge(1)),
+            (rect, 2.0, (40.0, 22.0), Edge(1)),
+            (rect, 2.0, (40.0, 17.9), Outside),
+            (rect, 2.0, (40.0, 22.1), Inside),
+            (rect, 2.0, (62.0, 35.0), Edge(2)),
+            (rect, 2.0, (58.0, 35.0), Edge(2)),
+            (rect, 2.0, (62.1, 35.0), Outside),
+            (rect, 2.0, (18.0, 35.0), Edge(4)),
+            (rect, 2.0, (17.9, 35.0), Outside),
+            (rect, 2.0, (21.9, 35.0), Edge(4)),
+            (rect, 2.0, (40.0, 52.0), Edge(6)),
+            (rect, 2.0, (40.0, 48.0), Edge(6)),
+            (rect, 2.0, (40.0, 52.1), Outside),
+            // corners, at the tolerance on both axes
+            (rect, 2.0, (18.0, 18.0), Edge(5)),
+            (rect, 2.0, (21.5, 21.5), Edge(5)),
+            (rect, 2.0, (62.0, 18.0), Edge(3)),
+            (rect, 2.0, (62.0, 52.0), Edge(8)),
+            (rect, 2.0, (18.0, 52.0), Edge(10)),
+            (rect, 2.0, (17.9, 18.0), Outside),
+            (rect, 2.0, (62.0, 17.9), Outside),
+            (rect, 2.0, (40.0, 35.0), Inside),
+            (rect, 2.0, (0.0, 0.0), Outside),
+            // no tolerance, only the edges themselves
+            (rect, 0.0, (20.0, 35.0), Edge(4)),
+            (rect, 0.0, (60.0, 50.0), Edge(8)),
+            (rect, 0.0, (20.5, 35.0), Inside),
+            (rect, 0.0, (19.5, 35.0), Outside),
+            // the closer edge wins on each axis, the low one on a tie
+            (tiny, 2.0, (21.5, 21.5), Edge(5)),
+            (tiny, 2.0, (22.0, 21.0), Edge(3)),
+            (tiny, 2.0, (22.0, 22.0), Edge(8)),
+            (tiny, 2.0, (21.4, 22.6), Edge(10)),
+            (tiny, 2.0, (21.5, 25.0), Edge(10)),
+            (tiny, 2.0, (21.5, 25.1), Outside),
+        ];
+        for (rect, tolerance, (x, y), expected) in cases {
+            assert_eq!(get_position_relation(rect, Pos2::new(x, y), 0.0, tolerance), expected, "{:?} ({}, {}) tolerance {}", rect, x, y, tolerance);
+        }
+
+        // corners win over the edge handles
+        let rect = Rect::from_min_size(Pos2::new(20.0, 20.0), Vec2::new(6.0, 6.0));
+        assert_eq!(get_position_relation(rect, Pos2::new(21.5, 19.0), 4.0, 2.0), PositionRelation::Edge(5));
+        assert_eq!(get_position_relation(rect, Pos2::new(23.0, 18.5), 4.0, 2.0), PositionRelation::Edge(1));
+    }
+
+    #[test]
+    fn handle_hit_test() {
+        let mut s = session();
+        s.set_hit_area(10.0, 0.0);
+        drag(&mut s, Pos2::new(100.0, 100.0), Pos2::new(300.0, 200.0));
+
+        // the handles are grabbed anywhere in their areas
+        assert_eq!(s.relation_at(Pos2::new(96.0, 104.0)), PositionRelation::Edge(5));
+        assert_eq!(s.relation_at(Pos2::new(204.0, 96.0)), PositionRelation::Edge(1));
+        assert_eq!(s.relation_at(Pos2::new(304.0, 150.0)), PositionRelation::Edge(2));
+        assert_eq!(s.relation_at(Pos2::new(303.0, 197.0)), PositionRelation::Edge(8));
+        assert_eq!(s.relation_at(Pos2::new(150.0, 150.0)), PositionRelation::Inside);
+        assert_eq!(s.relation_at(Pos2::new(90.0, 150.0)), PositionRelation::Outside);
+
+        // resize by the left handle, slightly off the edge
+        drag(&mut s, Pos2::new(97.0, 152.0), Pos2::new(47.0, 0.0));
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(50.0, 100.0), Pos2::new(300.0, 200.0))));
+    }
+
+    #[test]
+    fn move_inside_bounds_test() {
+        let bounds = Rect::from_min_max(Pos2::new(-100.0, 0.0), Pos2::new(1000.0, 800.0));
+        let rect = Rect::from_min_max(Pos2::new(0.0, 0.0), Pos2::new(200.0, 100.0));
+        let moved = |x: f32, y: f32| apply_move(rect, Vec2::new(x, y), bounds);
+
+        assert_eq!(moved(10.0, 20.0), rect.translate(Vec2::new(10.0, 20.0)));
+        assert_eq!(moved(-500.0, -500.0), Rect::from_min_max(Pos2::new(-100.0, 0.0), Pos2::new(100.0, 100.0)));
+        assert_eq!(moved(5000.0, 5000.0), Rect::from_min_max(Pos2::new(800.0, 700.0), Pos2::new(1000.0, 800.0)));
+
+        // the size is kept while dragging along the edge
+        let mut s = CropSession::new(bounds);
+        drag(&mut s, Pos2::new(0.0, 0.0), Pos2::new(200.0, 100.0));
+        drag(&mut s, Pos2::new(100.0, 50.0), Pos2::new(2000.0, 60.0));
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(800.0, 10.0), Pos2::new(1000.0, 110.0))));
+    }
+
+    #[test]
+    fn resize_flip_test() {
+        let bounds = Rect::from_min_max(Pos2::ZERO, Pos2::new(1000.0, 800.0));
+        let rect = Rect::from_min_max(Pos2::new(100.0, 100.0), Pos2::new(300.0, 200.0));
+
+        // the left edge past the right edge becomes the right edge
+        let (flipped, code) = apply_resize(rect, Vec2::new(250.0, 0.0), 4, bounds, Vec2::ZERO);
+        assert_eq!((flipped, code), (Rect::from_min_max(Pos2::new(300.0, 100.0), Pos2::new(350.0, 200.0)), 2));
+
+        // the top-left corner past both edges becomes the bottom-right corner
+        let (flipped, code) = apply_resize(rect, Vec2::new(300.0, 150.0), 5, bounds, Vec2::ZERO);
+        assert_eq!((flipped, code), (Rect::from_min_max(Pos2::new(300.0, 200.0), Pos2::new(400.0, 250.0)), 8));
+
+        // the bottom-left corner past the top edge only becomes the top-left corner, and stays in bounds
+        let (flipped, code) = apply_resize(rect, Vec2::new(-500.0, -300.0), 10, bounds, Vec2::ZERO);
+        assert_eq!((flipped, code), (Rect::from_min_max(Pos2::new(0.0, 0.0), Pos2::new(300.0, 100.0)), 5));
+
+        // the session goes on with the handed off code
+        let mut s = session();
+        drag(&mut s, Pos2::new(100.0, 100.0), Pos2::new(300.0, 200.0));
+        s.handle(SessionEvent::PointerPressed(Pos2::new(100.0, 150.0)));
+        s.handle(SessionEvent::PointerDragged(Pos2::new(350.0, 150.0)));
+        assert!(matches!(s.state(), AppState::Resizing(_, _, 2)));
+        s.handle(SessionEvent::PointerDragged(Pos2::new(400.0, 150.0)));
+        s.handle(SessionEvent::PointerReleased);
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(300.0, 100.0), Pos2::new(400.0, 200.0))));
+    }
+
+    #[test]
+    fn resize_min_size_test() {
+        let bounds = Rect::from_min_max(Pos2::ZERO, Pos2::new(1000.0, 800.0));
+        let rect = Rect::from_min_max(Pos2::new(100.0, 100.0), Pos2::new(300.0, 200.0));
+        let min_size = Vec2::new(20.0, 10.0);
+
+        // the moving edge stops short of the fixed one
+        let (resized, code) = apply_resize(rect, Vec2::new(195.0, 95.0), 5, bounds, min_size);
+        assert_eq!((resized, code), (Rect::from_min_max(Pos2::new(280.0, 190.0), Pos2::new(300.0, 200.0)), 5));
+
+        // and after flipping
+        let (resized, code) = apply_resize(rect, Vec2::new(-5.0, 0.0), 2, bounds, min_size);
+        assert_eq!(resized.width(), 195.0);
+        assert_eq!(code, 2);
+        let (resized, code) = apply_resize(rect, Vec2::new(-205.0, 0.0), 2, bounds, min_size);
+        assert_eq!((resized, code), (Rect::from_min_max(Pos2::new(80.0, 100.0), Pos2::new(100.0, 200.0)), 4));
+
+        // against the bounds, the fixed edge gives way
+        let rect = Rect::from_min_max(Pos2::new(5.0, 100.0), Pos2::new(300.0, 200.0));
+        let (resized, _) = apply_resize(rect, Vec2::new(-300.0, 0.0), 2, bounds, min_size);
+        assert_eq!(resized, Rect::from_min_max(Pos2::new(0.0, 100.0), Pos2::new(20.0, 200.0)));
+    }
+
+    #[test]
+    fn keyboard_test() {
+        let monitors = vec![
+            Rect::from_min_max(Pos2::ZERO, Pos2::new(500.0, 800.0)),
+            Rect::from_min_max(Pos2::new(500.0, 0.0), Pos2::new(1000.0, 800.0)),
+        ];
+        let windows = vec![
+            Rect::from_min_max(Pos2::new(10.0, 10.0), Pos2::new(20.0, 20.0)),
+            Rect::from_min_max(Pos2::new(30.0, 30.0), Pos2::new(40.0, 40.0)),
+        ];
+        let mut s = session().with_monitors(monitors.clone()).with_windows(windows.clone()).with_min_size(Vec2::new(5.0, 5.0));
+        let key = |s: &mut CropSession, key: SessionKey| s.handle(SessionEvent::KeyPressed(key));
+
+        // nothing to edit yet
+        key(&mut s, SessionKey::Nudge(1, 0));
+        assert_eq!(s.selection(), None);
+
+        // space, move, space
+        s.handle(SessionEvent::PointerMoved(Pos2::new(100.0, 100.0)));
+        key(&mut s, SessionKey::Toggle);
+        assert_eq!(s.state(), AppState::Cropping(Pos2::new(100.0, 100.0)));
+        s.handle(SessionEvent::PointerMoved(Pos2::new(200.0, 150.0)));
+        key(&mut s, SessionKey::Toggle);
+        assert_eq!(s.state(), AppState::Cropped);
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(100.0, 100.0), Pos2::new(200.0, 150.0))));
+
+        // nudge, in bounds
+        key(&mut s, SessionKey::Nudge(-10, 1));
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(90.0, 101.0), Pos2::new(190.0, 151.0))));
+        key(&mut s, SessionKey::Nudge(0, -1000));
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(90.0, 0.0), Pos2::new(190.0, 50.0))));
+
+        // resize, down to the minimum size
+        key(&mut s, SessionKey::Grow(10, -1));
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(90.0, 0.0), Pos2::new(200.0, 49.0))));
+        key(&mut s, SessionKey::Grow(-1000, 0));
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(90.0, 0.0), Pos2::new(95.0, 49.0))));
+
+        // the monitor under the pointer
+        s.handle(SessionEvent::PointerMoved(Pos2::new(600.0, 100.0)));
+        key(&mut s, SessionKey::SelectMonitor);
+        assert_eq!(s.selection(), Some(monitors[1]));
+
+        // cycle the windows
+        key(&mut s, SessionKey::NextWindow { reverse: false });
+        assert_eq!(s.selection(), Some(windows[0]));
+        key(&mut s, SessionKey::NextWindow { reverse: false });
+        assert_eq!(s.selection(), Some(windows[1]));
+        key(&mut s, SessionKey::NextWindow { reverse: false });
+        assert_eq!(s.selection(), Some(windows[0]));
+        key(&mut s, SessionKey::NextWindow { reverse: true });
+        assert_eq!(s.selection(), Some(windows[1]));
+
+        // the keys do not interfere with a drag
+        s.handle(SessionEvent::PointerPressed(Pos2::new(35.0, 35.0)));
+        key(&mut s, SessionKey::Nudge(1, 1));
+        key(&mut s, SessionKey::SelectMonitor);
+        assert!(matches!(s.state(), AppState::Moving(_, _)));
+        assert_eq!(s.selection(), Some(windows[1]));
+    }
+
+    #[test]
+    fn click_without_drag_test() {
+        let mut s = session();
+        s.handle(SessionEvent::PointerPressed(Pos2::new(10.0, 10.0)));
+        s.handle(SessionEvent::PointerDragged(Pos2::new(10.0, 10.0)));
+        s.handle(SessionEvent::PointerReleased);
+        assert_eq!(s.state(), AppState::Idle);
+        assert_eq!(s.selection(), None);
+
+        // nothing to confirm
+        assert_eq!(s.handle(SessionEvent::KeyPressed(SessionKey::Enter)), SessionOutcome::Pending);
+        assert_eq!(s.handle(SessionEvent::KeyPressed(SessionKey::Escape)), SessionOutcome::Cancelled);
+    }
+
+    #[test]
+    fn auto_bounding_test() {
+        let top = Rect::from_min_max(Pos2::new(100.0, 100.0), Pos2::new(300.0, 300.0));
+        let bottom = Rect::from_min_max(Pos2::new(200.0, 200.0), Pos2::new(1200.0, 500.0));
+        let mut s = session().with_windows(vec![top, bottom]);
+
+        // the topmost window under the pointer is highlighted
+        s.handle(SessionEvent::PointerMoved(Pos2::new(250.0, 250.0)));
+        assert_eq!(s.hovered(), Some(top));
+        // the window is clipped to the canvas
+        s.handle(SessionEvent::PointerMoved(Pos2::new(900.0, 400.0)));
+        let clipped = Rect::from_min_max(Pos2::new(200.0, 200.0), Pos2::new(1000.0, 500.0));
+        assert_eq!(s.hovered(), Some(clipped));
+        s.handle(SessionEvent::PointerMoved(Pos2::new(50.0, 50.0)));
+        assert_eq!(s.hovered(), None);
+
+        // a click selects the window, a drag still crops freely
+        s.handle(SessionEvent::PointerMoved(Pos2::new(900.0, 400.0)));
+        s.handle(SessionEvent::PointerPressed(Pos2::new(900.0, 400.0)));
+        assert_eq!(s.hovered(), None);
+        s.handle(SessionEvent::PointerReleased);
+        assert_eq!(s.state(), AppState::Cropped);
+        assert_eq!(s.selection(), Some(clipped));
+
+        // no more highlight once there is a crop area
+        s.handle(SessionEvent::PointerMoved(Pos2::new(250.0, 250.0)));
+        assert_eq!(s.hovered(), None);
+
+        let mut s = session().with_windows(vec![top]);
+        drag(&mut s, Pos2::new(150.0, 150.0), Pos2::new(160.0, 170.0));
+        assert_eq!(s.selection(), Some(Rect::from_min_max(Pos2::new(150.0, 150.0), Pos2::new(160.0, 170.0))));
+
+        // a click outside of any window selects nothing
+        let mut s = session().with_windows(vec![top]);
+        drag(&mut s, Pos2::new(500.0, 500.0), Pos2::new(500.0, 500.0));
+        assert_eq!(s.state(), AppState::Idle);
+        assert_eq!(s.selection(), None);
+    }
+
+    #[test]
+    fn unexpected_event_order_test() {
+        let events = [
+            SessionEvent::PointerReleased,
+            SessionEvent::PointerDragged(Pos2::new(20.0, 20.0)),
+            SessionEvent::PointerPressed(Pos2::new(20.0, 20.0)),
+            SessionEvent::PointerPressed(Pos2::new(50.0, 60.0)),
+            SessionEvent::PointerDragged(Pos2::new(-50.0, 2000.0)),
+            SessionEvent::PointerPressed(Pos2::new(30.0, 30.0)),
+            SessionEvent::PointerReleased,
+            SessionEvent::PointerReleased,
+            SessionEvent::PointerDragged(Pos2::new(1.0, 1.0)),
+        ];
+
+        // no rotation of the events should panic
+        for start in 0..events.len() {
+            let mut s = session();
+            for event in events.iter().cycle().skip(start).take(events.len() * 2) {
+                s.handle(*event);
+            }
+        }
+
+        // a second press without release finishes the first drag
+        let mut s = session();
+        s.handle(SessionEvent::PointerPressed(Pos2::new(10.0, 10.0)));
+        s.handle(SessionEvent::PointerDragged(Pos2::new(50.0, 50.0)));
+        s.handle(SessionEvent::PointerPressed(Pos2::new(30.0, 30.0)));
+        assert_eq!(s.state(), AppState::Moving(Rect::from_min_max(Pos2::new(10.0, 10.0), Pos2::new(50.0, 50.0)), Pos2::new(30.0, 30.0)));
+    }
+}
@@ -1,3 +1,39 @@
+//! Take screenshots of the screens, monitors and windows, with or without the interactive cropper.
+//!
+//! Start with [`CaptureRequest`]: pick a [`CaptureTarget`], tune it with the `with_*` methods, then run it.
+//! The building blocks ([`Snapper`], [`Snapshot`], [`Output`], ...) are available for finer control.
+//!
+//! # Stability
+//!
+//! The items exported here are the public API, they follow semver once the crate reaches 1.0.
+//! Before that, breaking changes only happen in minor versions (0.x), never in patch versions.
+//!
+//! - [`CaptureError`] may gain variants in any version, match it with a wildcard arm.
+//! - [`AppInfo`] may gain fields in minor versions, build it with `..Default::default()`.
+//! - The archive format of [`Snapshot::save`] stays readable by later versions.
+//!
+//! The modules themselves are private, anything not re-exported here may change at any time.
+
 mod canonical;
 mod cropper;
+mod output;
+mod request;
 mod snapper;
+
+use image::RgbaImage;
+pub use canonical::{
+    AppInfo, CaptureError, CoordinateSpace, Cursor, CursorScale, DesktopSpace, LogicalPos, LogicalRect, PhysicalPos, PhysicalRect,
+    Redaction, ScalePolicy, ScreenInfo, Snapshot, ARCHIVE_EXTENSION, XYWH,
+};
+pub use cropper::CropperConfig;
+pub use output::{NamingContext, NamingTemplate, Output, OutputFormat};
+pub use request::{Capture, CaptureRequest, CaptureTarget, Countdown, SeriesError};
+pub use snapper::{AppSelector, CaptureBackend, PrivacyFilter, PrivacyRule, Snapper, SyntheticBackend, XCapBackend};
+
+/// Capture the region without showing the cropper.
+///
+/// - `xywh`: x, y (top-left corner, in desktop coordinates) and width, height
+/// - `fill`: color (in RGBA format) of the area not covered by any monitor
+pub fn capture_region(xywh: (i32, i32, u32, u32), fill: [u8; 4]) -> Result<RgbaImage, CaptureError> {
+    snapper::Snapper::capture_region(&snapper::XCapBackend, xywh, fill)
+}
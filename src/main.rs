@@ -1,11 +1,7 @@
-#![windows_subsystem = "windows"]
+mod cli;
 
-mod canonical;
-mod cropper;
-mod snapper;
-
-fn main() {
-    cropper::Cropper::exec(Default::default()).unwrap();
+fn main() -> std::process::ExitCode {
+    cli::run()
 }
 
 // 窗口大于屏幕时, resize 会导致窗口被剪切为屏幕大小 (突变)
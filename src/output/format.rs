@@ -0,0 +1,76 @@
+use std::io::Cursor;
+use std::path::Path;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageFormat, RgbaImage};
+use crate::canonical::CaptureError;
+
+/// Supported formats of the output file.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum OutputFormat {
+    #[default]
+    Png,
+    /// quality: 1 ~ 100
+    Jpeg(u8),
+    /// lossless WebP
+    WebP,
+    Bmp,
+    Tiff,
+    Qoi,
+}
+
+impl OutputFormat {
+    pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+    /// Guess the format from the extension of the path, jpeg uses the default quality.
+    pub fn from_path(path: impl AsRef<Path>) -> Option<OutputFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "png" => Some(OutputFormat::Png),
+            "jpg" | "jpeg" => Some(OutputFormat::Jpeg(OutputFormat::DEFAULT_JPEG_QUALITY)),
+            "webp" => Some(OutputFormat::WebP),
+            "bmp" => Some(OutputFormat::Bmp),
+            "tif" | "tiff" => Some(OutputFormat::Tiff),
+            "qoi" => Some(OutputFormat::Qoi),
+            _ => None,
+        }
+    }
+
+    /// The preferred file extension (without the dot).
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Png => "png",
+            OutputFormat::Jpeg(_) => "jpg",
+            OutputFormat::WebP => "webp",
+            OutputFormat::Bmp => "bmp",
+            OutputFormat::Tiff => "tiff",
+            OutputFormat::Qoi => "qoi",
+        }
+    }
+
+    fn image_format(&self) -> ImageFormat {
+        match self {
+            OutputFormat::Png => ImageFormat::Png,
+            OutputFormat::Jpeg(_) => ImageFormat::Jpeg,
+            OutputFormat::WebP => ImageFormat::WebP,
+            OutputFormat::Bmp => ImageFormat::Bmp,
+            OutputFormat::Tiff => ImageFormat::Tiff,
+            OutputFormat::Qoi => ImageFormat::Qoi,
+        }
+    }
+
+    /// Encode the image into the buffer of this format.
+    pub fn encode(&self, image: &RgbaImage) -> Result<Vec<u8>, CaptureError> {
+        let mut buffer = Cursor::new(vec![]);
+        match self {
+            OutputFormat::Jpeg(quality) => {
+                // jpeg has no alpha channel
+                let rgb = DynamicImage::ImageRgba8(image.clone()).to_rgb8();
+                let encoder = JpegEncoder::new_with_quality(&mut buffer, (*quality).clamp(1, 100));
+                rgb.write_with_encoder(encoder)
+            }
+            _ => image.write_to(&mut buffer, self.image_format()),
+        }?;
+
+        Ok(buffer.into_inner())
+    }
+}
@@ -0,0 +1,185 @@
+mod format;
+mod template;
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local};
+use image::RgbaImage;
+use crate::canonical::CaptureError;
+pub use format::OutputFormat;
+pub use template::{NamingContext, NamingTemplate};
+
+/// Write images to the disk, with the path given by a [`NamingTemplate`].
+///
+/// - the extension of the format is appended if the rendered path does not have it
+/// - missing directories are created
+/// - existing files are kept (unless `overwrite` is set): the counter `{n}` is bumped if the template
+///   has one, otherwise a suffix `-1`, `-2`, ... is added to the file name
+#[derive(Clone, Debug)]
+pub struct Output {
+    template: NamingTemplate,
+    format: OutputFormat,
+    overwrite: bool,
+    /// next value of the `{n}` token
+    counter: u32,
+}
+
+impl Default for Output {
+    fn default() -> Output {
+        Output::new(NamingTemplate::default(), None)
+    }
+}
+
+impl Output {
+    /// Create an output, the format is guessed from the template when omitted (PNG by default).
+    pub fn new(template: NamingTemplate, format: Option<OutputFormat>) -> Output {
+        let format = format
+            .or_else(|| OutputFormat::from_path(template.as_str()))
+            .unwrap_or_default();
+
+        Output {
+            template,
+            format,
+            overwrite: false,
+            counter: 1,
+        }
+    }
+
+    pub fn with_overwrite(mut self, overwrite: bool) -> Output {
+        self.overwrite = overwrite;
+        self
+    }
+
+    pub fn format(&self) -> OutputFormat {
+        self.format
+    }
+
+    /// Encode the image and write it to the next free path, return the path.
+    ///
+    /// Unless `overwrite` is set, the file is created only if it does not exist yet, so outputs running at the same
+    /// time (e.g. two processes) never write to the same file.
+    pub fn save(&mut self, image: &RgbaImage, context: &NamingContext) -> Result<PathBuf, CaptureError> {
+        let buffer = self.format.encode(image)?;
+        let now = Local::now();
+
+        let first = self.render(context, &now, self.counter);
+        let mut path = first.clone();
+        let mut suffix = 0;
+        loop {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)?;
+            }
+            if self.overwrite {
+                fs::write(&path, &buffer)?;
+                break;
+            }
+
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    if let Err(err) = file.write_all(&buffer) {
+                        let _ = fs::remove_file(&path);
+                        return Err(err.into());
+                    }
+                    break;
+                }
+                // taken, by an existing file or a concurrent output: the counter is bumped if the template has one
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    if self.template.has_counter() {
+                        self.counter += 1;
+                        path = self.render(context, &now, self.counter);
+                    } else {
+                        suffix += 1;
+                        path = with_suffix(&first, suffix);
+                    }
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        self.counter += 1;
+        Ok(path)
+    }
+
+    /// Render the template into a path, with the extension of the format.
+    fn render(&self, context: &NamingContext, now: &DateTime<Local>, counter: u32) -> PathBuf {
+        let mut path = PathBuf::from(self.template.render(context, now, counter));
+        if OutputFormat::from_path(&path).map(|f| f.extension()) != Some(self.format.extension()) {
+            let mut name = path.file_name().unwrap_or_default().to_os_string();
+            name.push(".");
+            name.push(self.format.extension());
+            path.set_file_name(name);
+        }
+        path
+    }
+}
+
+/// `name.ext` to `name-suffix.ext`.
+fn with_suffix(path: &Path, suffix: u32) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = path.extension().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}-{}.{}", stem, suffix, extension))
+}
+
+#[cfg(test)]
+mod unit_test {
+    use super::*;
+
+    #[test]
+    fn render_test() {
+        let context = NamingContext {
+            monitor: Some("DISPLAY 1".into()),
+            app: Some("Code".into()),
+            title: Some("a/b: c?".into()),
+            width: 640,
+            height: 480,
+        };
+        let now = Local::now();
+        let template = NamingTemplate::new("{app}_{title}_{monitor}_{w}x{h}_{n}_{unknown}_{date}");
+
+        assert_eq!(
+            template.render(&context, &now, 7),
+            format!("Code_a_b_ c__DISPLAY 1_640x480_7_{{unknown}}_{}", now.format("%Y-%m-%d"))
+        );
+        assert_eq!(NamingTemplate::new("{w").render(&context, &now, 1), "{w");
+        assert_eq!(NamingTemplate::new("abc{w").render(&context, &now, 1), "abc{w");
+        assert_eq!(NamingTemplate::new("{").render(&context, &now, 1), "{");
+    }
+
+    #[test]
+    fn save_test() {
+        let dir = std::env::temp_dir().join(format!("capture-output-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let image = RgbaImage::new(4, 3);
+        let context = NamingContext::default();
+
+        // collisions are resolved with the counter
+        let mut output = Output::new(NamingTemplate::new(dir.join("nested/shot-{n}").to_string_lossy()), None);
+        assert_eq!(output.save(&image, &context).unwrap(), dir.join("nested/shot-1.png"));
+        assert_eq!(output.save(&image, &context).unwrap(), dir.join("nested/shot-2.png"));
+        let mut output = Output::new(NamingTemplate::new(dir.join("nested/shot-{n}").to_string_lossy()), None);
+        assert_eq!(output.save(&image, &context).unwrap(), dir.join("nested/shot-3.png"));
+
+        // a file taken after the output picked its counter is not overwritten
+        fs::write(dir.join("nested/shot-4.png"), "taken").unwrap();
+        assert_eq!(output.save(&image, &context).unwrap(), dir.join("nested/shot-5.png"));
+        assert_eq!(fs::read_to_string(dir.join("nested/shot-4.png")).unwrap(), "taken");
+        let mut output = output.with_overwrite(true);
+        assert_eq!(output.save(&image, &context).unwrap(), dir.join("nested/shot-6.png"));
+
+        // or with a suffix
+        let mut output = Output::new(NamingTemplate::new(dir.join("fixed.jpeg").to_string_lossy()), None);
+        assert_eq!(output.format(), OutputFormat::Jpeg(OutputFormat::DEFAULT_JPEG_QUALITY));
+        assert_eq!(output.save(&image, &context).unwrap(), dir.join("fixed.jpeg"));
+        assert_eq!(output.save(&image, &context).unwrap(), dir.join("fixed-1.jpeg"));
+
+        // every format can be encoded and decoded back
+        for format in [OutputFormat::WebP, OutputFormat::Bmp, OutputFormat::Tiff, OutputFormat::Qoi] {
+            let mut output = Output::new(NamingTemplate::new(dir.join("all").to_string_lossy()), Some(format));
+            let path = output.save(&image, &context).unwrap();
+            assert_eq!(image::open(path).unwrap().width(), 4);
+        }
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,99 @@
+use chrono::{DateTime, Local};
+
+/// Values to fill in the tokens of a [`NamingTemplate`].
+#[derive(Clone, Default, Debug)]
+pub struct NamingContext {
+    /// name of the monitor
+    pub monitor: Option<String>,
+    /// name of the application
+    pub app: Option<String>,
+    /// title of the window
+    pub title: Option<String>,
+    /// size of the image
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Template of the output path.
+///
+/// | Token       | Value                                  |
+/// |-------------|----------------------------------------|
+/// | `{date}`    | local date, `YYYY-MM-DD`               |
+/// | `{time}`    | local time, `HH-MM-SS`                 |
+/// | `{monitor}` | name of the monitor                    |
+/// | `{app}`     | name of the application                |
+/// | `{title}`   | title of the window                    |
+/// | `{w}`       | width of the image                     |
+/// | `{h}`       | height of the image                    |
+/// | `{n}`       | counter, bumped until the path is free |
+///
+/// Unknown tokens are kept as they are, missing values are replaced with an empty string.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct NamingTemplate(String);
+
+impl Default for NamingTemplate {
+    fn default() -> NamingTemplate {
+        NamingTemplate::new("capture-{date}-{time}")
+    }
+}
+
+impl NamingTemplate {
+    pub fn new(template: impl Into<String>) -> NamingTemplate {
+        NamingTemplate(template.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Whether the template contains the counter token `{n}`.
+    pub fn has_counter(&self) -> bool {
+        self.0.contains("{n}")
+    }
+
+    pub fn render(&self, context: &NamingContext, now: &DateTime<Local>, counter: u32) -> String {
+        let mut rendered = String::with_capacity(self.0.len());
+        let mut rest = self.0.as_str();
+
+        while let Some(start) = rest.find('{') {
+            rendered.push_str(&rest[..start]);
+            let Some(len) = rest[start..].find('}') else {
+                rendered.push_str(&rest[start..]);
+                rest = "";
+                break;
+            };
+
+            let token = &rest[start + 1..start + len];
+            let value = match token {
+                "date" => Some(now.format("%Y-%m-%d").to_string()),
+                "time" => Some(now.format("%H-%M-%S").to_string()),
+                "monitor" => Some(sanitize(context.monitor.as_deref())),
+                "app" => Some(sanitize(context.app.as_deref())),
+                "title" => Some(sanitize(context.title.as_deref())),
+                "w" => Some(context.width.to_string()),
+                "h" => Some(context.height.to_string()),
+                "n" => Some(counter.to_string()),
+                _ => None,
+            };
+            match value {
+                Some(value) => rendered.push_str(&value),
+                None => rendered.push_str(&rest[start..=start + len]),
+            }
+
+            rest = &rest[start + len + 1..];
+        }
+        rendered.push_str(rest);
+
+        rendered
+    }
+}
+
+/// Make the value safe to be used in a file name.
+fn sanitize(value: Option<&str>) -> String {
+    value.unwrap_or_default()
+        .trim()
+        .chars()
+        .map(|c| if c.is_control() || r#"<>:"/\|?*"#.contains(c) { '_' } else { c })
+        .take(100)
+        .collect()
+}
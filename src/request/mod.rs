@@ -0,0 +1,426 @@
+mod timer;
+
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use image::RgbaImage;
+use crate::canonical::{AppInfo, CaptureError, CursorScale, ScalePolicy, ScreenInfo, Snapshot, XYWH};
+use crate::cropper::{Cropper, CropperConfig};
+use crate::output::{NamingContext, Output};
+use crate::snapper::{AppSelector, CaptureBackend, PrivacyFilter, Snapper, XCapBackend};
+pub use timer::Countdown;
+
+/// What to capture.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CaptureTarget {
+    /// a region cropped by the user with the interactive UI
+    Interactive,
+    /// a region of the desktop, in desktop coordinates
+    Region(XYWH),
+    /// the whole virtual desktop
+    Full(ScalePolicy),
+    /// a single monitor, by name, index (in the order of [`Snapshot::screens`]) or part of the name
+    Monitor(String),
+    /// a single window
+    Window(AppSelector),
+}
+
+/// The result of a [`CaptureRequest`].
+#[derive(Debug)]
+pub struct Capture {
+    pub image: RgbaImage,
+    /// the windows on the desktop, from the topmost to the bottommost, empty unless requested
+    pub apps: Vec<AppInfo>,
+    /// the captured window, for [`CaptureTarget::Window`] only
+    pub app: Option<AppInfo>,
+    /// the path of the written file, if an output is given
+    pub path: Option<PathBuf>,
+}
+
+/// A series of captures stopped by an error, see [`CaptureRequest::exec_series`].
+#[derive(Debug)]
+pub struct SeriesError {
+    /// the captures taken before the error, whose files (if any) are already written
+    pub captures: Vec<Capture>,
+    pub error: CaptureError,
+}
+
+impl Display for SeriesError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} (after {} captures)", self.error, self.captures.len())
+    }
+}
+
+impl Error for SeriesError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl From<CaptureError> for SeriesError {
+    fn from(error: CaptureError) -> Self {
+        SeriesError { captures: vec![], error }
+    }
+}
+
+/// Describe a capture, then run it with [`CaptureRequest::exec`].
+///
+/// ```no_run
+/// use capture::{CaptureRequest, CaptureTarget, NamingTemplate, Output};
+///
+/// let capture = CaptureRequest::new(CaptureTarget::Interactive)
+///     .with_auto_bounding(true)
+///     .with_mask_color([0, 0, 0, 160])
+///     .with_output(Output::new(NamingTemplate::new("shots/{date}-{n}.png"), None))
+///     .exec()?;
+/// if let Some(capture) = capture {
+///     println!("{:?}", capture.path);
+/// }
+/// # Ok::<(), capture::CaptureError>(())
+/// ```
+pub struct CaptureRequest {
+    target: CaptureTarget,
+    with_app_info: bool,
+    fill: [u8; 4],
+    cropper_config: CropperConfig,
+    privacy: PrivacyFilter,
+    output: Option<Output>,
+    delay: Duration,
+    countdown: Option<Countdown>,
+    /// how to paint the cursor, `None` to leave it out
+    cursor: Option<CursorScale>,
+}
+
+impl CaptureRequest {
+    pub fn new(target: CaptureTarget) -> CaptureRequest {
+        CaptureRequest {
+            target,
+            with_app_info: false,
+            fill: [0, 0, 0, 0],
+            cropper_config: CropperConfig::default(),
+            privacy: PrivacyFilter::default(),
+            output: None,
+            delay: Duration::ZERO,
+            countdown: None,
+            cursor: None,
+        }
+    }
+
+    /// Collect the info of the windows into [`Capture::apps`].
+    pub fn with_app_info(mut self, with_app_info: bool) -> CaptureRequest {
+        self.with_app_info = with_app_info;
+        self
+    }
+
+    /// Color (in RGBA format) of the area not covered by any monitor, transparent by default.
+    pub fn with_fill(mut self, fill: [u8; 4]) -> CaptureRequest {
+        self.fill = fill;
+        self
+    }
+
+    /// Bound the window under the mouse, for [`CaptureTarget::Interactive`].
+    pub fn with_auto_bounding(mut self, auto_bounding: bool) -> CaptureRequest {
+        self.cropper_config.auto_bounding = auto_bounding;
+        self
+    }
+
+    /// Color (in RGBA format) of the mask outside of the crop area, for [`CaptureTarget::Interactive`].
+    pub fn with_mask_color(mut self, mask_color: [u8; 4]) -> CaptureRequest {
+        self.cropper_config.mask_color = mask_color;
+        self
+    }
+
+    /// All the settings of the cropper, for [`CaptureTarget::Interactive`]. It replaces the ones given
+    /// by [`CaptureRequest::with_auto_bounding`] and [`CaptureRequest::with_mask_color`] before.
+    pub fn with_cropper_config(mut self, cropper_config: CropperConfig) -> CaptureRequest {
+        self.cropper_config = cropper_config;
+        self
+    }
+
+    /// Write the image to the disk as well, see [`Capture::path`].
+    pub fn with_output(mut self, output: Output) -> CaptureRequest {
+        self.output = Some(output);
+        self
+    }
+
+    /// Hide the windows matching the filter, before the pixels reach the cropper or the output.
+    pub fn with_privacy(mut self, privacy: PrivacyFilter) -> CaptureRequest {
+        self.privacy = privacy;
+        self
+    }
+
+    /// Include the mouse cursor, it is left out by default.
+    pub fn with_cursor(mut self, with_cursor: bool) -> CaptureRequest {
+        self.cursor = with_cursor.then(|| self.cursor.unwrap_or_default());
+        self
+    }
+
+    /// How big the cursor is, see [`CursorScale`]. It also includes the cursor.
+    pub fn with_cursor_scale(mut self, scale: CursorScale) -> CaptureRequest {
+        self.cursor = Some(scale);
+        self
+    }
+
+    /// Wait before taking the snapshot, e.g. to open a menu. No delay by default.
+    pub fn with_delay(mut self, delay: Duration) -> CaptureRequest {
+        self.delay = delay;
+        self
+    }
+
+    /// Show the seconds left of the delay, see [`Countdown`].
+    pub fn with_countdown(mut self, countdown: impl FnMut(u32) + 'static) -> CaptureRequest {
+        self.countdown = Some(Box::new(countdown));
+        self
+    }
+
+    /// Capture the live screens, `None` if the user cancels the interactive crop.
+    pub fn exec(self) -> Result<Option<Capture>, CaptureError> {
+        self.exec_with(&XCapBackend)
+    }
+
+    /// Same as [`CaptureRequest::exec`], but with the given backend.
+    pub fn exec_with(mut self, backend: &dyn CaptureBackend) -> Result<Option<Capture>, CaptureError> {
+        timer::wait(self.delay, &mut self.countdown)?;
+        self.shoot(backend)
+    }
+
+    /// Capture the live screens `shots` times, every `interval` after the delay. Each image is written
+    /// to the output (if any), so a template with the counter `{n}` names the frames in order.
+    ///
+    /// A cancelled interactive crop ends the series. An error ends it as well, with the captures taken
+    /// before it in [`SeriesError::captures`]. A series running beyond the range of the clock is refused
+    /// with [`CaptureError::InvalidRequest`] before anything is captured.
+    pub fn exec_series(self, interval: Duration, shots: u32) -> Result<Vec<Capture>, SeriesError> {
+        self.exec_series_with(&XCapBackend, interval, shots)
+    }
+
+    /// Same as [`CaptureRequest::exec_series`], but with the given backend.
+    pub fn exec_series_with(mut self, backend: &dyn CaptureBackend, interval: Duration, shots: u32) -> Result<Vec<Capture>, SeriesError> {
+        // the last shot is the latest time to reach
+        let length = interval.checked_mul(shots.saturating_sub(1))
+            .ok_or_else(|| CaptureError::InvalidRequest(format!("{} shots every {:?} is too long", shots, interval)))?;
+        timer::after(Instant::now(), self.delay.saturating_add(length))?;
+        timer::wait(self.delay, &mut self.countdown)?;
+
+        let start = Instant::now();
+        let mut captures = vec![];
+        for shot in 0..shots {
+            timer::wait_until(start + interval * shot);
+            match self.shoot(backend) {
+                Ok(Some(capture)) => captures.push(capture),
+                Ok(None) => break,
+                Err(error) => return Err(SeriesError { captures, error }),
+            }
+        }
+        Ok(captures)
+    }
+
+    /// Take a snapshot and capture the target out of it.
+    fn shoot(&mut self, backend: &dyn CaptureBackend) -> Result<Option<Capture>, CaptureError> {
+        let with_app_info = self.with_app_info
+            || matches!(self.target, CaptureTarget::Window(_))
+            || (self.target == CaptureTarget::Interactive && self.cropper_config.auto_bounding);
+        let mut snapshot = Snapper::take_snapshot_filtered(backend, with_app_info, &self.privacy)?;
+        if let Some(scale) = self.cursor {
+            Snapper::draw_cursor(backend, &mut snapshot, scale)?;
+        }
+        let apps = if self.with_app_info { snapshot.apps.clone() } else { vec![] };
+
+        let mut app = None;
+        let (image, context) = match &self.target {
+            CaptureTarget::Interactive => {
+                match Cropper::exec_on(snapshot, self.cropper_config.clone())? {
+                    Some(image) => (image, NamingContext::default()),
+                    None => return Ok(None),
+                }
+            }
+            CaptureTarget::Region(xywh) => (snapshot.crop_with_fill(*xywh, self.fill), NamingContext::default()),
+            CaptureTarget::Full(policy) => (snapshot.compose(*policy), NamingContext::default()),
+            CaptureTarget::Monitor(target) => {
+                let screen = find_monitor(&snapshot, target)
+                    .ok_or_else(|| CaptureError::NotFound(format!("no monitor matches '{}'", target)))?;
+                let context = NamingContext {
+                    monitor: Some(screen.name.clone()),
+                    ..Default::default()
+                };
+                (screen.rgba_image.clone(), context)
+            }
+            CaptureTarget::Window(selector) => {
+                let mut found = Snapper::capture_app_in(backend, &snapshot, selector)?
+                    .ok_or_else(|| CaptureError::NotFound(format!("no window matches {}", selector)))?;
+                // the window alone is captured by the backend, which knows nothing about the filter
+                self.privacy.apply_to_app(&mut found);
+                let image = found.rgba_image.take()
+                    .ok_or_else(|| CaptureError::NotFound(format!("window '{}' is off-screen", found.title)))?;
+                let context = NamingContext {
+                    app: Some(found.name.clone()),
+                    title: Some(found.title.clone()),
+                    ..Default::default()
+                };
+                app = Some(found);
+                (image, context)
+            }
+        };
+
+        let path = match &mut self.output {
+            Some(output) => {
+                let context = NamingContext {
+                    width: image.width(),
+                    height: image.height(),
+                    ..context
+                };
+                Some(output.save(&image, &context)?)
+            }
+            None => None,
+        };
+
+        Ok(Some(Capture { image, apps, app, path }))
+    }
+}
+
+/// The monitor by index, by name, or by part of the name (case-insensitive).
+fn find_monitor<'a>(snapshot: &'a Snapshot, target: &str) -> Option<&'a ScreenInfo> {
+    match target.parse::<usize>() {
+        Ok(index) => snapshot.screens.get(index),
+        Err(_) => snapshot.screens.iter().find(|s| s.name == target)
+            .or_else(|| snapshot.screens.iter().find(|s| s.name.to_lowercase().contains(&target.to_lowercase()))),
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use image::Rgba;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use crate::canonical::{Cursor, PhysicalPos, Redaction};
+    use crate::output::NamingTemplate;
+    use crate::snapper::SyntheticBackend;
+    use super::*;
+
+    #[test]
+    fn capture_request_test() {
+        let screen = |name: &str, x: i32, color: [u8; 4]| ScreenInfo {
+            name: name.into(),
+            is_primary: x == 0,
+            xywh: (x, 0, 10, 10),
+            sf: 1.0,
+            rgba_image: RgbaImage::from_pixel(10, 10, Rgba(color)),
+        };
+        let app = AppInfo {
+            name: "Editor".into(),
+            title: "notes.txt".into(),
+            xywh: (5, 2, 10, 5),
+            id: 7,
+            ..Default::default()
+        };
+        let backend = SyntheticBackend::new(
+            vec![screen("DISPLAY-1", 0, [1, 0, 0, 255]), screen("DISPLAY-2", 10, [2, 0, 0, 255])],
+            vec![app],
+        );
+
+        let capture = CaptureRequest::new(CaptureTarget::Monitor("display-2".into()))
+            .exec_with(&backend).unwrap().unwrap();
+        assert_eq!(capture.image.get_pixel(0, 0).0, [2, 0, 0, 255]);
+        assert!(capture.apps.is_empty() && capture.app.is_none() && capture.path.is_none());
+
+        let capture = CaptureRequest::new(CaptureTarget::Region((-2, 0, 4, 1)))
+            .with_fill([9, 9, 9, 9])
+            .with_app_info(true)
+            .exec_with(&backend).unwrap().unwrap();
+        assert_eq!(capture.image.get_pixel(0, 0).0, [9, 9, 9, 9]);
+        assert_eq!(capture.image.get_pixel(3, 0).0, [1, 0, 0, 255]);
+        assert_eq!(capture.apps.len(), 1);
+
+        let capture = CaptureRequest::new(CaptureTarget::Window(AppSelector::Id(7)))
+            .exec_with(&backend).unwrap().unwrap();
+        assert_eq!(capture.image.dimensions(), (10, 5));
+        assert_eq!(capture.app.unwrap().title, "notes.txt");
+
+        // the cursor is only there on request, and refused when the backend can not tell where it is
+        let result = CaptureRequest::new(CaptureTarget::Monitor("1".into())).with_cursor(true).exec_with(&backend);
+        assert!(matches!(result, Err(CaptureError::Backend(_))));
+        let backend = backend.with_cursor(Cursor { position: PhysicalPos { x: 12.0, y: 3.0 }, image: None });
+        let capture = CaptureRequest::new(CaptureTarget::Monitor("1".into())).exec_with(&backend).unwrap().unwrap();
+        assert_eq!(capture.image.get_pixel(2, 3).0, [2, 0, 0, 255]);
+        let capture = CaptureRequest::new(CaptureTarget::Monitor("1".into()))
+            .with_cursor(true)
+            .exec_with(&backend).unwrap().unwrap();
+        assert_eq!(capture.image.get_pixel(2, 3).0, [0, 0, 0, 255]);
+
+        // the hidden window is blacked out before it reaches the result, even when it is the target
+        let privacy = PrivacyFilter::new().hide("notes", Redaction::Blackout);
+        let capture = CaptureRequest::new(CaptureTarget::Region((0, 0, 20, 10)))
+            .with_privacy(privacy.clone())
+            .exec_with(&backend).unwrap().unwrap();
+        assert_eq!(capture.image.get_pixel(5, 2).0, [0, 0, 0, 255]);
+        assert_eq!(capture.image.get_pixel(14, 6).0, [0, 0, 0, 255]);
+        assert_eq!(capture.image.get_pixel(4, 2).0, [1, 0, 0, 255]);
+        assert!(capture.apps.is_empty());
+        let capture = CaptureRequest::new(CaptureTarget::Window(AppSelector::Id(7)))
+            .with_privacy(privacy)
+            .exec_with(&backend).unwrap().unwrap();
+        assert_eq!(capture.image.get_pixel(9, 4).0, [0, 0, 0, 255]);
+
+        let result = CaptureRequest::new(CaptureTarget::Monitor("3".into())).exec_with(&backend);
+        assert!(matches!(result, Err(CaptureError::NotFound(_))));
+    }
+
+    #[test]
+    fn exec_series_test() {
+        let dir = std::env::temp_dir().join(format!("capture-series-test-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let screen = ScreenInfo {
+            name: "main".into(),
+            is_primary: true,
+            xywh: (0, 0, 4, 4),
+            sf: 1.0,
+            rgba_image: RgbaImage::new(4, 4),
+        };
+        let backend = SyntheticBackend::new(vec![screen], vec![]);
+        let ticks = Rc::new(RefCell::new(vec![]));
+        let recorder = ticks.clone();
+
+        let start = Instant::now();
+        let captures = CaptureRequest::new(CaptureTarget::Full(ScalePolicy::Native))
+            .with_delay(Duration::from_millis(20))
+            .with_countdown(move |seconds| recorder.borrow_mut().push(seconds))
+            .with_output(Output::new(NamingTemplate::new(dir.join("frame-{n}.png").to_string_lossy()), None))
+            .exec_series_with(&backend, Duration::from_millis(10), 3)
+            .unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+        assert_eq!(*ticks.borrow(), vec![1]);
+
+        // frames are written through the template, in order
+        let paths: Vec<_> = captures.iter().map(|capture| capture.path.clone().unwrap()).collect();
+        assert_eq!(paths, (1..=3).map(|n| dir.join(format!("frame-{}.png", n))).collect::<Vec<_>>());
+        assert!(paths.iter().all(|path| path.exists()));
+
+        // refused before anything is taken, instead of an overflow
+        let result = CaptureRequest::new(CaptureTarget::Full(ScalePolicy::Native))
+            .exec_series_with(&backend, Duration::MAX, 2);
+        assert!(matches!(result, Err(SeriesError { ref captures, error: CaptureError::InvalidRequest(_) }) if captures.is_empty()));
+
+        // the frames before an error are kept
+        struct Failing(SyntheticBackend, std::cell::Cell<u32>);
+        impl CaptureBackend for Failing {
+            fn screens(&self) -> Result<Vec<ScreenInfo>, CaptureError> {
+                self.1.set(self.1.get() + 1);
+                if self.1.get() > 2 { Err(CaptureError::NoMonitors) } else { self.0.screens() }
+            }
+            fn apps(&self) -> Result<Vec<AppInfo>, CaptureError> {
+                self.0.apps()
+            }
+        }
+        let result = CaptureRequest::new(CaptureTarget::Full(ScalePolicy::Native))
+            .with_output(Output::new(NamingTemplate::new(dir.join("partial-{n}.png").to_string_lossy()), None))
+            .exec_series_with(&Failing(backend, Default::default()), Duration::ZERO, 3);
+        let Err(SeriesError { captures, error: CaptureError::NoMonitors }) = result else {
+            panic!("the third shot fails");
+        };
+        assert_eq!(captures.len(), 2);
+        assert!(captures.iter().all(|capture| capture.path.as_ref().unwrap().exists()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,75 @@
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::canonical::CaptureError;
+
+/// Called before a delayed capture, with the whole seconds left (rounded up, at most `u32::MAX`), once per second.
+pub type Countdown = Box<dyn FnMut(u32)>;
+
+/// Sleep for the delay, ticking the countdown at every whole second left.
+///
+/// Fails with [`CaptureError::InvalidRequest`] if the delay is beyond the range of the clock.
+pub(crate) fn wait(delay: Duration, countdown: &mut Option<Countdown>) -> Result<(), CaptureError> {
+    let end = after(Instant::now(), delay)?;
+    loop {
+        let left = end.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Ok(());
+        }
+
+        let seconds = left.as_secs() + u64::from(left.subsec_nanos() > 0);
+        if let Some(countdown) = countdown.as_mut() {
+            countdown(countdown_seconds(seconds));
+        }
+        // down to the next whole second
+        thread::sleep(left - Duration::from_secs(seconds - 1));
+    }
+}
+
+/// The seconds told to the countdown, which stays at the largest value instead of wrapping around.
+fn countdown_seconds(seconds: u64) -> u32 {
+    u32::try_from(seconds).unwrap_or(u32::MAX)
+}
+
+/// The time after the duration, an error if it is beyond the range of the clock.
+pub(crate) fn after(time: Instant, duration: Duration) -> Result<Instant, CaptureError> {
+    time.checked_add(duration)
+        .ok_or_else(|| CaptureError::InvalidRequest(format!("{:?} is too long to wait", duration)))
+}
+
+/// Sleep until the time, if it is not passed yet.
+pub(crate) fn wait_until(time: Instant) {
+    thread::sleep(time.saturating_duration_since(Instant::now()));
+}
+
+#[cfg(test)]
+mod unit_test {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use super::*;
+
+    #[test]
+    fn wait_test() {
+        let ticks = Rc::new(RefCell::new(vec![]));
+        let recorder = ticks.clone();
+        let mut countdown: Option<Countdown> = Some(Box::new(move |seconds| recorder.borrow_mut().push(seconds)));
+
+        wait(Duration::ZERO, &mut countdown).unwrap();
+        assert!(ticks.borrow().is_empty());
+
+        let start = Instant::now();
+        wait(Duration::from_millis(30), &mut countdown).unwrap();
+        assert!(start.elapsed() >= Duration::from_millis(30));
+        assert_eq!(*ticks.borrow(), vec![1]);
+
+        wait(Duration::from_millis(10), &mut None).unwrap();
+
+        // an error instead of an overflow
+        assert!(matches!(wait(Duration::MAX, &mut countdown), Err(CaptureError::InvalidRequest(_))));
+        assert_eq!(*ticks.borrow(), vec![1]);
+
+        // a long delay is told as the largest value, not wrapped around
+        assert_eq!(countdown_seconds(5), 5);
+        assert_eq!(countdown_seconds(u32::MAX as u64 + 1), u32::MAX);
+        assert_eq!(countdown_seconds(u64::MAX), u32::MAX);
+    }
+}
@@ -0,0 +1,320 @@
+use image::RgbaImage;
+use xcap::{Monitor, Window, XCapError};
+use crate::canonical::{AppInfo, CaptureError, Cursor, ScreenInfo, Snapshot};
+
+/// Where the screens and apps of a snapshot come from.
+///
+/// [`XCapBackend`] is the default one, which talks to the real display.
+/// [`SyntheticBackend`] yields in-memory fixtures, so that everything built on top of
+/// [`Snapper`](super::Snapper) can run without a live display (e.g. in CI).
+pub trait CaptureBackend {
+    /// Info & screenshot of each monitor.
+    fn screens(&self) -> Result<Vec<ScreenInfo>, CaptureError>;
+
+    /// Info of each monitor, without the screenshot (`rgba_image` is empty).
+    ///
+    /// The default takes the screenshots and drops them, backends which can tell the monitors cheaply should override it.
+    fn monitors(&self) -> Result<Vec<ScreenInfo>, CaptureError> {
+        let mut screens = self.screens()?;
+        for screen in &mut screens {
+            screen.rgba_image = RgbaImage::new(0, 0);
+        }
+        Ok(screens)
+    }
+
+    /// Info of each window, from the topmost to the bottommost.
+    fn apps(&self) -> Result<Vec<AppInfo>, CaptureError>;
+
+    /// The pixels of the window with the given id, including the parts covered by other windows.
+    ///
+    /// `None` if the backend can not capture a single window, or the window is gone, the caller falls back to the screens then.
+    fn capture_window(&self, _id: u32) -> Result<Option<RgbaImage>, CaptureError> {
+        Ok(None)
+    }
+
+    /// The mouse cursor, `None` if the backend can not tell where it is (drawing the cursor fails then).
+    fn cursor(&self) -> Result<Option<Cursor>, CaptureError> {
+        Ok(None)
+    }
+}
+
+/// Tell the refusals of the platform from the other failures of `xcap`.
+fn backend_error(err: XCapError) -> CaptureError {
+    #[cfg(target_os = "linux")]
+    if let XCapError::StdIOError(err) = &err {
+        if err.kind() == std::io::ErrorKind::PermissionDenied {
+            return CaptureError::PermissionDenied(err.to_string());
+        }
+    }
+
+    // macOS and the portals on wayland only describe it in the message
+    let message = err.to_string();
+    if message.to_lowercase().contains("permission") {
+        CaptureError::PermissionDenied(message)
+    } else {
+        CaptureError::Backend(Box::new(err))
+    }
+}
+
+/// Capture the real monitors and windows with `xcap`.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct XCapBackend;
+
+impl CaptureBackend for XCapBackend {
+    fn screens(&self) -> Result<Vec<ScreenInfo>, CaptureError> {
+        // monitor info
+        let monitors = Monitor::all().map_err(backend_error)?;
+
+        // info & screenshot of each monitor
+        let mut screens = vec![];
+        for monitor in monitors {
+            screens.push(ScreenInfo {
+                name: monitor.name().into(),
+                is_primary: monitor.is_primary(),
+                xywh: (monitor.x(), monitor.y(), monitor.width(), monitor.height()),
+                sf: monitor.scale_factor(),
+                rgba_image: monitor.capture_image().map_err(backend_error)?,
+            });
+        }
+
+        Ok(screens)
+    }
+
+    fn monitors(&self) -> Result<Vec<ScreenInfo>, CaptureError> {
+        let monitors = Monitor::all().map_err(backend_error)?;
+        Ok(monitors.into_iter()
+            .map(|monitor| ScreenInfo {
+                name: monitor.name().into(),
+                is_primary: monitor.is_primary(),
+                xywh: (monitor.x(), monitor.y(), monitor.width(), monitor.height()),
+                sf: monitor.scale_factor(),
+                rgba_image: RgbaImage::new(0, 0),
+            })
+            .collect())
+    }
+
+    fn apps(&self) -> Result<Vec<AppInfo>, CaptureError> {
+        // window info
+        let windows = Window::all().map_err(backend_error)?;
+        let ids: Vec<u32> = windows.iter().map(|window| window.id()).collect();
+        let (zs, pids) = window_details(&ids);
+
+        let mut apps = vec![];
+        for ((window, z), pid) in windows.into_iter().zip(zs).zip(pids) {
+            apps.push(AppInfo {
+                name: window.app_name().into(),
+                title: window.title().into(),
+                is_minimized: window.is_minimized(),
+                xywh: (window.x(), window.y(), window.width(), window.height()),
+                z,
+                id: window.id(),
+                pid,
+                monitor: window.current_monitor().name().into(),
+                ..Default::default()
+            });
+        }
+
+        Ok(apps)
+    }
+
+    fn capture_window(&self, id: u32) -> Result<Option<RgbaImage>, CaptureError> {
+        let windows = Window::all().map_err(backend_error)?;
+        match windows.into_iter().find(|window| window.id() == id) {
+            Some(window) => window.capture_image().map(Some).map_err(backend_error),
+            None => Ok(None),
+        }
+    }
+
+    /// `xcap` has no cursor API, it is asked from the platform directly: with its image from the X server
+    /// (through XFixes) on Linux, `None` on wayland without XWayland. Only the position is known on Windows
+    /// and macOS, where the built-in arrow is drawn.
+    fn cursor(&self) -> Result<Option<Cursor>, CaptureError> {
+        #[cfg(target_os = "linux")]
+        {
+            x11_cursor()
+        }
+        #[cfg(target_os = "windows")]
+        {
+            use windows::Win32::Foundation::POINT;
+            use windows::Win32::UI::WindowsAndMessaging::GetCursorPos;
+            use crate::canonical::PhysicalPos;
+
+            let mut point = POINT::default();
+            unsafe { GetCursorPos(&mut point) }.map_err(|err| CaptureError::Backend(Box::new(err)))?;
+            Ok(Some(Cursor { position: PhysicalPos { x: point.x as f32, y: point.y as f32 }, image: None }))
+        }
+        #[cfg(target_os = "macos")]
+        {
+            use core_graphics::event::CGEvent;
+            use core_graphics::event_source::{CGEventSource, CGEventSourceStateID};
+            use crate::canonical::PhysicalPos;
+
+            // an empty event carries the current location, in points from the top-left of the main display
+            let location = CGEventSource::new(CGEventSourceStateID::CombinedSessionState)
+                .and_then(CGEvent::new)
+                .map(|event| event.location());
+            Ok(location.ok().map(|point| Cursor { position: PhysicalPos { x: point.x as f32, y: point.y as f32 }, image: None }))
+        }
+        #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+        {
+            Ok(None)
+        }
+    }
+}
+
+/// The stacking order (0 is the topmost) and the owner process of the windows with the ids, which `xcap` does not expose.
+///
+/// `xcap` lists the windows from the topmost on Windows and macOS. On Linux it lists them in the mapping order
+/// (`_NET_CLIENT_LIST`), so the order is read from `_NET_CLIENT_LIST_STACKING` instead, and the process from `_NET_WM_PID`.
+/// The process is not known on macOS.
+fn window_details(ids: &[u32]) -> (Vec<Option<u32>>, Vec<Option<u32>>) {
+    #[cfg(target_os = "linux")]
+    {
+        x11_window_details(ids)
+    }
+    #[cfg(target_os = "windows")]
+    {
+        use windows::Win32::Foundation::HWND;
+        use windows::Win32::UI::WindowsAndMessaging::GetWindowThreadProcessId;
+
+        let pids = ids.iter()
+            .map(|&id| {
+                let mut pid = 0;
+                unsafe { GetWindowThreadProcessId(HWND(id as isize), Some(&mut pid)) };
+                (pid != 0).then_some(pid)
+            })
+            .collect();
+        ((0..ids.len() as u32).map(Some).collect(), pids)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        ((0..ids.len() as u32).map(Some).collect(), vec![None; ids.len()])
+    }
+}
+
+/// The values of a property with 32-bit elements of the window, `None` if it is not set.
+#[cfg(target_os = "linux")]
+fn x11_property(conn: &xcb::Connection, window: xcb::x::Window, name: &[u8]) -> Option<Vec<u32>> {
+    use xcb::x;
+
+    let cookie = conn.send_request(&x::InternAtom { only_if_exists: true, name });
+    let atom = conn.wait_for_reply(cookie).ok()?.atom();
+    if atom == x::ATOM_NONE {
+        return None;
+    }
+    let cookie = conn.send_request(&x::GetProperty {
+        delete: false,
+        window,
+        property: atom,
+        r#type: x::ATOM_ANY,
+        long_offset: 0,
+        long_length: u32::MAX,
+    });
+    let reply = conn.wait_for_reply(cookie).ok()?;
+    (reply.format() == 32 && reply.r#type() != x::ATOM_NONE).then(|| reply.value::<u32>().to_vec())
+}
+
+/// See [`window_details`], the stacking order is unknown for every window if the window manager does not tell.
+#[cfg(target_os = "linux")]
+fn x11_window_details(ids: &[u32]) -> (Vec<Option<u32>>, Vec<Option<u32>>) {
+    use xcb::{x, Connection, XidNew};
+
+    let Ok((conn, screen)) = Connection::connect(None) else {
+        return (vec![None; ids.len()], vec![None; ids.len()]);
+    };
+    let root = conn.get_setup().roots().nth(screen as usize).map(|screen| screen.root());
+
+    // from the bottom
+    let stacking = root.and_then(|root| x11_property(&conn, root, b"_NET_CLIENT_LIST_STACKING"));
+    let zs = ids.iter()
+        .map(|id| {
+            let stacking = stacking.as_ref()?;
+            let index = stacking.iter().position(|window| window == id)?;
+            Some((stacking.len() - 1 - index) as u32)
+        })
+        .collect();
+    let pids = ids.iter()
+        .map(|&id| {
+            let window = x::Window::new(id);
+            x11_property(&conn, window, b"_NET_WM_PID")?.first().copied()
+        })
+        .collect();
+
+    (zs, pids)
+}
+
+/// The cursor with its image from the X server, `None` if there is no X server.
+#[cfg(target_os = "linux")]
+fn x11_cursor() -> Result<Option<Cursor>, CaptureError> {
+    use xcb::{xfixes, Connection, Extension};
+    use crate::canonical::PhysicalPos;
+
+    let Ok((conn, _)) = Connection::connect_with_extensions(None, &[Extension::XFixes], &[]) else {
+        return Ok(None);
+    };
+    let error = |err: xcb::Error| CaptureError::Backend(Box::new(err));
+
+    // the version has to be told before any other request of the extension
+    let cookie = conn.send_request(&xfixes::QueryVersion { client_major_version: 4, client_minor_version: 0 });
+    conn.wait_for_reply(cookie).map_err(error)?;
+    let cookie = conn.send_request(&xfixes::GetCursorImage {});
+    let reply = conn.wait_for_reply(cookie).map_err(error)?;
+
+    // premultiplied ARGB
+    let pixels = reply.cursor_image().iter().flat_map(|argb| {
+        let [b, g, r, a] = argb.to_le_bytes();
+        let unpremultiply = |c: u8| if a == 0 { 0 } else { (c as u32 * 255 / a as u32).min(255) as u8 };
+        [unpremultiply(r), unpremultiply(g), unpremultiply(b), a]
+    }).collect();
+    let image = RgbaImage::from_raw(reply.width() as u32, reply.height() as u32, pixels);
+
+    Ok(Some(Cursor {
+        position: PhysicalPos { x: reply.x() as f32, y: reply.y() as f32 },
+        image: image.map(|image| (image, (reply.xhot() as u32, reply.yhot() as u32))),
+    }))
+}
+
+/// An in-memory backend which yields (copies of) the given screens and apps.
+#[derive(Clone, Default, Debug)]
+pub struct SyntheticBackend {
+    pub screens: Vec<ScreenInfo>,
+    pub apps: Vec<AppInfo>,
+    pub cursor: Option<Cursor>,
+}
+
+impl SyntheticBackend {
+    pub fn new(screens: Vec<ScreenInfo>, apps: Vec<AppInfo>) -> SyntheticBackend {
+        SyntheticBackend { screens, apps, cursor: None }
+    }
+
+    pub fn with_cursor(mut self, cursor: Cursor) -> SyntheticBackend {
+        self.cursor = Some(cursor);
+        self
+    }
+}
+
+/// Replay a snapshot, e.g. one loaded from an archive.
+impl From<Snapshot> for SyntheticBackend {
+    fn from(snapshot: Snapshot) -> SyntheticBackend {
+        SyntheticBackend::new(snapshot.screens, snapshot.apps)
+    }
+}
+
+impl CaptureBackend for SyntheticBackend {
+    fn screens(&self) -> Result<Vec<ScreenInfo>, CaptureError> {
+        Ok(self.screens.clone())
+    }
+
+    fn apps(&self) -> Result<Vec<AppInfo>, CaptureError> {
+        Ok(self.apps.clone())
+    }
+
+    /// The `rgba_image` of the app with the id, if given.
+    fn capture_window(&self, id: u32) -> Result<Option<RgbaImage>, CaptureError> {
+        Ok(self.apps.iter().find(|app| app.id == id).and_then(|app| app.rgba_image.clone()))
+    }
+
+    fn cursor(&self) -> Result<Option<Cursor>, CaptureError> {
+        Ok(self.cursor.clone())
+    }
+}
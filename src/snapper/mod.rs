@@ -1,60 +1,144 @@
-use xcap::{Monitor, Window, XCapError};
-use crate::canonical::{AppInfo, ScreenInfo, Snapshot};
+mod backend;
+mod privacy;
+
+use std::fmt::{Display, Formatter};
+use image::RgbaImage;
+use crate::canonical::{intersect, AppInfo, CaptureError, CursorScale, ScreenInfo, Snapshot, XYWH};
+pub use backend::{CaptureBackend, SyntheticBackend, XCapBackend};
+pub use privacy::{PrivacyFilter, PrivacyRule};
+
+/// How to pick a window.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum AppSelector {
+    /// the window id given by the platform
+    Id(u32),
+    /// (part of) the title or the app name, case-insensitive
+    Title(String),
+}
+
+impl AppSelector {
+    pub fn matches(&self, app: &AppInfo) -> bool {
+        match self {
+            AppSelector::Id(id) => app.id == *id,
+            AppSelector::Title(pattern) => {
+                let pattern = pattern.to_lowercase();
+                app.title.to_lowercase().contains(&pattern) || app.name.to_lowercase().contains(&pattern)
+            }
+        }
+    }
+}
+
+impl Display for AppSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppSelector::Id(id) => write!(f, "id {}", id),
+            AppSelector::Title(pattern) => write!(f, "'{}'", pattern),
+        }
+    }
+}
 
 pub struct Snapper;
 
 impl Snapper {
     /// Take a snapshot of the screens.
-    fn _screens() -> Result<Vec<ScreenInfo>, XCapError> {
-        // monitor info
-        let monitors = Monitor::all()?;
-
-        // info & screenshot of each monitor
-        let mut screens = vec![];
-        for monitor in monitors {
-            screens.push(ScreenInfo {
-                name: monitor.name().into(),
-                is_primary: monitor.is_primary(),
-                xywh: (monitor.x(), monitor.y(), monitor.width(), monitor.height()),
-                sf: monitor.scale_factor(),
-                rgba_image: monitor.capture_image()?,
-            });
+    fn _screens(backend: &dyn CaptureBackend) -> Result<Vec<ScreenInfo>, CaptureError> {
+        backend.screens()
+    }
+
+    /// Take a snapshot of the apps.
+    fn _apps(backend: &dyn CaptureBackend) -> Result<Vec<AppInfo>, CaptureError> {
+        backend.apps()
+    }
+
+    /// Take a snapshot of the screens and apps(if with_app_info is true).
+    pub fn take_snapshot(with_app_info: bool) -> Result<Snapshot, CaptureError> {
+        Snapper::take_snapshot_with(&XCapBackend, with_app_info)
+    }
+
+    /// Same as [`Snapper::take_snapshot`], but with the given backend.
+    pub fn take_snapshot_with(backend: &dyn CaptureBackend, with_app_info: bool) -> Result<Snapshot, CaptureError> {
+        let screens = Snapper::_screens(backend)?;
+        let apps = if with_app_info { Snapper::_apps(backend)? } else { vec![] };
+
+        Snapshot::new(screens, apps)
+    }
+
+    /// Same as [`Snapper::take_snapshot_with`], but the windows matching the filter are hidden on the screens.
+    /// The apps are always taken for the filter, but they are dropped unless `with_app_info` is true.
+    pub fn take_snapshot_filtered(backend: &dyn CaptureBackend, with_app_info: bool, filter: &PrivacyFilter) -> Result<Snapshot, CaptureError> {
+        if filter.is_empty() {
+            return Snapper::take_snapshot_with(backend, with_app_info);
         }
 
-        Ok(screens)
+        let mut snapshot = Snapper::take_snapshot_with(backend, true)?;
+        filter.apply(&mut snapshot);
+        if !with_app_info {
+            snapshot.apps.clear();
+        }
+        Ok(snapshot)
     }
 
-    /// Take a snapshot of the apps.
-    fn _apps() -> Result<Vec<AppInfo>, XCapError> {
-        // window info
-        let windows = Window::all()?;
-
-        // info & screenshot of each window
-        let mut apps = vec![];
-        for window in windows {
-            apps.push(AppInfo {
-                name: window.app_name().into(),
-                title: window.title().into(),
-                is_minimized: window.is_minimized(),
-                xywh: (window.x(), window.y(), window.width(), window.height()),
-            });
+    /// Paint the cursor onto the screens of the snapshot, which are never taken with it.
+    /// The built-in arrow is used if the backend knows where the cursor is but not how it looks,
+    /// it is an error if the backend does not know either.
+    pub fn draw_cursor(backend: &dyn CaptureBackend, snapshot: &mut Snapshot, scale: CursorScale) -> Result<(), CaptureError> {
+        match backend.cursor()? {
+            Some(cursor) => snapshot.draw_cursor(&cursor, scale),
+            None => Err(CaptureError::Backend("the cursor position is not available".into())),
         }
+    }
 
-        Ok(apps)
+    /// Capture the region (in desktop coordinates) without any UI,
+    /// the area not covered by any screen is filled with the given color (in RGBA format).
+    pub fn capture_region(backend: &dyn CaptureBackend, xywh: XYWH, fill: [u8; 4]) -> Result<RgbaImage, CaptureError> {
+        let snapshot = Snapper::take_snapshot_with(backend, false)?;
+        Ok(snapshot.crop_with_fill(xywh, fill))
     }
 
-    /// Take a snapshot of the screens and apps(if with_app_info is true).
-    pub fn take_snapshot(with_app_info: bool) -> Result<Snapshot, String> {
-        match Snapper::_screens() {
-            Ok(screens) => if with_app_info {
-                match Snapper::_apps() {
-                    Ok(apps) => Ok(Snapshot::new(screens, apps)),
-                    Err(err2) => Err(format!("{:?}", err2)),
-                }
-            } else {
-                Ok(Snapshot::new(screens, vec![]))
-            }
-            Err(err1) => Err(format!("{:?}", err1))
+    /// Capture the topmost visible (not minimized nor off-screen) window matching the selector,
+    /// `None` if there is no such window.
+    ///
+    /// The image is in `rgba_image` of the result. It is the window alone, even when it is partially covered,
+    /// if the backend supports capturing a single window. Otherwise it is cut from the screens, as seen on the desktop.
+    ///
+    /// The window is picked on the monitors alone, their screenshots are only taken for the fallback.
+    pub fn capture_app(backend: &dyn CaptureBackend, selector: &AppSelector) -> Result<Option<AppInfo>, CaptureError> {
+        let layout = Snapshot::new(backend.monitors()?, Snapper::_apps(backend)?)?;
+        let Some(app) = Snapper::find_app(&layout, selector) else {
+            return Ok(None);
+        };
+        if let Some(image) = backend.capture_window(app.id)? {
+            return Ok(Some(AppInfo { rgba_image: Some(image), ..app.clone() }));
+        }
+
+        let snapshot = Snapshot::new(Snapper::_screens(backend)?, vec![])?;
+        Ok(Some(Snapper::cut_app(&snapshot, app)))
+    }
+
+    /// Same as [`Snapper::capture_app`], but pick the window from a snapshot (taken with the app info) of the backend.
+    pub fn capture_app_in(backend: &dyn CaptureBackend, snapshot: &Snapshot, selector: &AppSelector) -> Result<Option<AppInfo>, CaptureError> {
+        let Some(app) = Snapper::find_app(snapshot, selector) else {
+            return Ok(None);
+        };
+
+        match backend.capture_window(app.id)? {
+            Some(image) => Ok(Some(AppInfo { rgba_image: Some(image), ..app.clone() })),
+            None => Ok(Some(Snapper::cut_app(snapshot, app))),
+        }
+    }
+
+    /// The topmost visible window of the snapshot matching the selector.
+    fn find_app<'a>(snapshot: &'a Snapshot, selector: &AppSelector) -> Option<&'a AppInfo> {
+        snapshot.apps.iter()
+            .filter(|app| !app.is_minimized && !app.is_off_screen)
+            .find(|app| selector.matches(app))
+    }
+
+    /// The app with its image cut from the screens.
+    fn cut_app(snapshot: &Snapshot, app: &AppInfo) -> AppInfo {
+        AppInfo {
+            rgba_image: intersect(app.xywh, snapshot.xywh).map(|xywh| snapshot.crop_native(xywh)),
+            ..app.clone()
         }
     }
 }
@@ -81,4 +165,114 @@ mod unit_test {
 
         println!("Elapsed: {:?}", now.elapsed());
     }
+
+    #[test]
+    fn take_snapshot_with_synthetic_backend_test() {
+        let screen = |name: &str, x: i32, y: i32, w: u32, h: u32| ScreenInfo {
+            name: name.into(),
+            is_primary: x == 0 && y == 0,
+            xywh: (x, y, w, h),
+            sf: 1.0,
+            rgba_image: RgbaImage::new(w, h),
+        };
+        let app = AppInfo {
+            name: "app".into(),
+            title: "title".into(),
+            is_minimized: false,
+            xywh: (10, 10, 100, 100),
+            z: Some(0),
+            id: 42,
+            ..Default::default()
+        };
+        let backend = SyntheticBackend::new(
+            vec![screen("left", -800, 200, 800, 600), screen("main", 0, 0, 1920, 1080)],
+            vec![app],
+        );
+
+        let snapshot = Snapper::take_snapshot_with(&backend, false).unwrap();
+        assert_eq!(snapshot.xywh, (-800, 0, 2720, 1080));
+        assert_eq!(snapshot.screens.len(), 2);
+        assert!(snapshot.apps.is_empty());
+
+        let snapshot = Snapper::take_snapshot_with(&backend, true).unwrap();
+        assert_eq!(snapshot.apps.len(), 1);
+
+        // no screen is an error, not a panic
+        let empty = SyntheticBackend::new(vec![], vec![]);
+        assert!(matches!(Snapper::take_snapshot_with(&empty, true), Err(CaptureError::NoMonitors)));
+
+        // metadata only, no pixels
+        assert_eq!(
+            serde_json::to_string(&snapshot).unwrap(),
+            r#"{"xywh":[-800,0,2720,1080],"screens":[{"name":"left","is_primary":false,"xywh":[-800,200,800,600],"sf":1.0},{"name":"main","is_primary":true,"xywh":[0,0,1920,1080],"sf":1.0}],"apps":[{"name":"app","title":"title","is_minimized":false,"xywh":[10,10,100,100],"z":0,"id":42,"pid":null,"monitor":"main","is_occluded":false,"is_off_screen":false}]}"#
+        );
+    }
+
+    #[test]
+    fn capture_app_test() {
+        let screen = ScreenInfo {
+            name: "main".into(),
+            is_primary: true,
+            xywh: (0, 0, 100, 100),
+            sf: 1.0,
+            rgba_image: RgbaImage::from_pixel(100, 100, image::Rgba([1, 1, 1, 255])),
+        };
+        let app = |id: u32, title: &str, xywh: XYWH| AppInfo {
+            name: "app".into(),
+            title: title.into(),
+            xywh,
+            z: Some(id),
+            id,
+            ..Default::default()
+        };
+        let mut editor = app(2, "Editor", (-10, 10, 30, 30));
+        editor.rgba_image = Some(RgbaImage::from_pixel(30, 30, image::Rgba([2, 2, 2, 255])));
+        let backend = SyntheticBackend::new(vec![screen], vec![app(1, "Terminal", (0, 0, 20, 20)), editor]);
+
+        // the backend captures the window alone
+        let captured = Snapper::capture_app(&backend, &AppSelector::Title("edit".into())).unwrap().unwrap();
+        assert_eq!(captured.id, 2);
+        assert_eq!(captured.rgba_image.unwrap().get_pixel(0, 0).0, [2, 2, 2, 255]);
+
+        // or the window is cut from the screens
+        let captured = Snapper::capture_app(&backend, &AppSelector::Id(1)).unwrap().unwrap();
+        assert_eq!(captured.title, "Terminal");
+        let image = captured.rgba_image.unwrap();
+        assert_eq!(image.dimensions(), (20, 20));
+        assert_eq!(image.get_pixel(0, 0).0, [1, 1, 1, 255]);
+
+        assert!(Snapper::capture_app(&backend, &AppSelector::Id(3)).unwrap().is_none());
+
+        // an off-screen window above the visible match is skipped, and the chosen one is cut
+        let mut hidden = app(0, "Editor (hidden)", (500, 500, 30, 30));
+        hidden.rgba_image = Some(RgbaImage::from_pixel(30, 30, image::Rgba([3, 3, 3, 255])));
+        let mut apps = backend.apps;
+        apps.push(hidden);
+        apps[0].title = "Editor notes".into();
+        let backend = SyntheticBackend::new(backend.screens, apps);
+        let captured = Snapper::capture_app(&backend, &AppSelector::Title("edit".into())).unwrap().unwrap();
+        assert_eq!(captured.id, 1);
+        assert_eq!(captured.rgba_image.unwrap().get_pixel(0, 0).0, [1, 1, 1, 255]);
+        assert!(Snapper::capture_app(&backend, &AppSelector::Id(0)).unwrap().is_none());
+
+        // the screens are not taken when the window is captured alone
+        struct NoScreens(SyntheticBackend);
+        impl CaptureBackend for NoScreens {
+            fn screens(&self) -> Result<Vec<ScreenInfo>, CaptureError> {
+                Err(CaptureError::NoMonitors)
+            }
+            fn monitors(&self) -> Result<Vec<ScreenInfo>, CaptureError> {
+                self.0.monitors()
+            }
+            fn apps(&self) -> Result<Vec<AppInfo>, CaptureError> {
+                self.0.apps()
+            }
+            fn capture_window(&self, id: u32) -> Result<Option<RgbaImage>, CaptureError> {
+                self.0.capture_window(id)
+            }
+        }
+        let backend = NoScreens(backend);
+        assert_eq!(Snapper::capture_app(&backend, &AppSelector::Id(2)).unwrap().unwrap().id, 2);
+        assert!(Snapper::capture_app(&backend, &AppSelector::Id(1)).is_err());
+    }
 }
\ No newline at end of file
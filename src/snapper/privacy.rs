@@ -0,0 +1,177 @@
+use std::fs;
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::canonical::{AppInfo, CaptureError, Redaction, Snapshot};
+use crate::snapper::AppSelector;
+
+/// Windows to hide, and how.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PrivacyRule {
+    /// (part of) the app name or the title, case-insensitive
+    pub pattern: String,
+    /// blackout by default
+    #[serde(default = "PrivacyRule::default_redaction")]
+    pub redaction: Redaction,
+}
+
+impl PrivacyRule {
+    fn default_redaction() -> Redaction {
+        Redaction::Blackout
+    }
+}
+
+/// Hide the windows matching the rules, before the pixels are used in any way.
+///
+/// It is read from JSON like `{"rules": [{"pattern": "password"}, {"pattern": "chat", "redaction": {"blur": {"sigma": 12.0}}}]}`.
+#[derive(Clone, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub struct PrivacyFilter {
+    /// the first matching rule is used
+    pub rules: Vec<PrivacyRule>,
+}
+
+impl PrivacyFilter {
+    pub fn new() -> PrivacyFilter {
+        PrivacyFilter::default()
+    }
+
+    /// Add a rule.
+    pub fn hide(mut self, pattern: impl Into<String>, redaction: Redaction) -> PrivacyFilter {
+        self.rules.push(PrivacyRule { pattern: pattern.into(), redaction });
+        self
+    }
+
+    /// Read the filter from a JSON file.
+    ///
+    /// A blur weaker than [`Redaction::MIN_SIGMA`] is refused, since the file asks for a redaction it would not get.
+    pub fn load(path: impl AsRef<Path>) -> Result<PrivacyFilter, CaptureError> {
+        let filter: PrivacyFilter = serde_json::from_slice(&fs::read(path)?)?;
+        for rule in &filter.rules {
+            if let Redaction::Blur { sigma } = rule.redaction {
+                if !(sigma >= Redaction::MIN_SIGMA && sigma.is_finite()) {
+                    return Err(CaptureError::invalid_data(format!(
+                        "the blur of '{}' is too weak, expect a sigma of at least {}", rule.pattern, Redaction::MIN_SIGMA
+                    )));
+                }
+            }
+        }
+        Ok(filter)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// How to hide the app, `None` if it is not hidden.
+    pub fn redaction_for(&self, app: &AppInfo) -> Option<Redaction> {
+        self.rules.iter()
+            .find(|rule| AppSelector::Title(rule.pattern.clone()).matches(app))
+            .map(|rule| rule.redaction)
+    }
+
+    /// Redact the windows matching the rules on the screens (and their own images, if any) of the snapshot,
+    /// which is taken with the app info.
+    ///
+    /// The whole window is redacted, including the parts covered by other windows. Only minimized windows are
+    /// skipped: the occluded ones are redacted as well, in case the stacking order is wrong.
+    pub fn apply(&self, snapshot: &mut Snapshot) {
+        let hidden: Vec<_> = snapshot.apps.iter()
+            .filter(|app| !app.is_minimized && !app.is_off_screen)
+            .filter_map(|app| self.redaction_for(app).map(|redaction| (app.xywh, redaction)))
+            .collect();
+        for (xywh, redaction) in hidden {
+            snapshot.redact(xywh, redaction);
+        }
+
+        for app in &mut snapshot.apps {
+            self.apply_to_app(app);
+        }
+    }
+
+    /// Redact the image of the app if it matches the rules.
+    pub fn apply_to_app(&self, app: &mut AppInfo) {
+        if let (Some(redaction), Some(image)) = (self.redaction_for(app), app.rgba_image.as_mut()) {
+            let (w, h) = image.dimensions();
+            redaction.apply(image, (0, 0, w, h));
+        }
+    }
+}
+
+#[cfg(test)]
+mod unit_test {
+    use image::{Rgba, RgbaImage};
+    use crate::canonical::ScreenInfo;
+    use super::*;
+
+    #[test]
+    fn privacy_filter_test() {
+        let app = |id: u32, name: &str, title: &str, xywh| AppInfo {
+            name: name.into(),
+            title: title.into(),
+            xywh,
+            z: Some(id),
+            id,
+            ..Default::default()
+        };
+        let mut vault = app(1, "Vault", "Passwords", (2, 2, 4, 4));
+        vault.rgba_image = Some(RgbaImage::from_pixel(4, 4, Rgba([9, 9, 9, 255])));
+        let mut chat = app(3, "Chat", "team", (0, 0, 10, 10));
+        chat.is_minimized = true;
+        // said to be below the editor, which covers it
+        let hidden_vault = app(4, "Vault", "Passwords", (7, 7, 2, 2));
+        let mut snapshot = Snapshot::new(
+            vec![ScreenInfo {
+                name: "main".into(),
+                is_primary: true,
+                xywh: (0, 0, 10, 10),
+                sf: 1.0,
+                rgba_image: RgbaImage::from_pixel(10, 10, Rgba([200, 200, 200, 255])),
+            }],
+            vec![vault, app(2, "Editor", "notes.txt", (5, 5, 5, 5)), chat, hidden_vault],
+        ).unwrap();
+        assert!(snapshot.apps[3].is_occluded);
+
+        // rules from JSON, blackout by default
+        let json = r#"{"rules": [{"pattern": "password"}, {"pattern": "CHAT", "redaction": {"pixelate": {"block": 4}}}]}"#;
+        let filter: PrivacyFilter = serde_json::from_str(json).unwrap();
+        assert_eq!(filter, PrivacyFilter::new().hide("password", Redaction::Blackout).hide("CHAT", Redaction::Pixelate { block: 4 }));
+        assert_eq!(filter.redaction_for(&snapshot.apps[1]), None);
+
+        // the chat is minimized, so it is left alone, but the occluded vault is hidden
+        filter.apply(&mut snapshot);
+        let pixel = |x, y| snapshot.screens[0].rgba_image.get_pixel(x, y).0;
+        assert_eq!(pixel(2, 2), [0, 0, 0, 255]);
+        assert_eq!(pixel(5, 5), [0, 0, 0, 255]);
+        assert_eq!(pixel(6, 6), [200, 200, 200, 255]);
+        // the occluded one as well
+        assert_eq!(pixel(7, 7), [0, 0, 0, 255]);
+        assert_eq!(pixel(8, 8), [0, 0, 0, 255]);
+        assert_eq!(pixel(9, 9), [200, 200, 200, 255]);
+        assert_eq!(pixel(1, 1), [200, 200, 200, 255]);
+        assert_eq!(snapshot.apps[0].rgba_image.as_ref().unwrap().get_pixel(3, 3).0, [0, 0, 0, 255]);
+    }
+
+    #[test]
+    fn blur_rule_test() {
+        let path = std::env::temp_dir().join(format!("capture-privacy-test-{}.json", std::process::id()));
+
+        // a weak blur is refused when loading
+        for sigma in ["0", "0.2", "-1"] {
+            fs::write(&path, format!(r#"{{"rules": [{{"pattern": "vault", "redaction": {{"blur": {{"sigma": {}}}}}}}]}}"#, sigma)).unwrap();
+            assert!(matches!(PrivacyFilter::load(&path), Err(CaptureError::Io(_))), "sigma {}", sigma);
+        }
+        fs::write(&path, r#"{"rules": [{"pattern": "vault", "redaction": {"blur": {"sigma": 4}}}]}"#).unwrap();
+        let filter = PrivacyFilter::load(&path).unwrap();
+        let _ = fs::remove_file(&path);
+
+        // the pixels of the matched window really change
+        let checker = RgbaImage::from_fn(10, 10, |x, y| if (x + y) % 2 == 0 { Rgba([0, 0, 0, 255]) } else { Rgba([255, 255, 255, 255]) });
+        let mut snapshot = Snapshot::new(
+            vec![ScreenInfo { name: "main".into(), is_primary: true, xywh: (0, 0, 10, 10), sf: 1.0, rgba_image: checker.clone() }],
+            vec![AppInfo { name: "Vault".into(), xywh: (0, 0, 5, 10), z: Some(0), id: 1, ..Default::default() }],
+        ).unwrap();
+        filter.apply(&mut snapshot);
+        let changed = |x: u32, y: u32| snapshot.screens[0].rgba_image.get_pixel(x, y).0[0].abs_diff(checker.get_pixel(x, y).0[0]) > 64;
+        assert!((0..5).all(|x| (0..10).all(|y| changed(x, y))));
+        assert!((5..10).all(|x| (0..10).all(|y| !changed(x, y))));
+    }
+}